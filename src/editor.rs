@@ -29,14 +29,15 @@ impl Completer for EditHelper {
             for dir in env::split_paths(&paths) {
                 if let Ok(entries) = std::fs::read_dir(dir) {
                     for entry in entries.flatten() {
-                        if let Ok(file_name) = entry.file_name().into_string() {
-                            if file_name.starts_with(&word) {
-                                let full_path = entry.path();
-                                if full_path.is_file() && is_exec(&full_path) {
-                                    if !candidates.iter().any(|c| c == &file_name) {
-                                        candidates.push(file_name);
-                                    }
-                                }
+                        if let Ok(file_name) = entry.file_name().into_string()
+                            && file_name.starts_with(&word)
+                        {
+                            let full_path = entry.path();
+                            if full_path.is_file()
+                                && is_exec(&full_path)
+                                && !candidates.iter().any(|c| c == &file_name)
+                            {
+                                candidates.push(file_name);
                             }
                         }
                     }
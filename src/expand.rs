@@ -0,0 +1,819 @@
+use crate::{
+    parser::assign::is_name,
+    state::{Shell, Var, with_shell},
+    tokenizer::Token,
+    utils::{glob_paths, pattern_match},
+};
+use std::mem;
+
+enum Value {
+    Scalar(String),
+    At(Vec<String>),   // $@
+    Star(Vec<String>), // $*
+}
+
+#[derive(PartialEq)]
+enum Mode {
+    Split, // command arguments
+    Join,  // assignment values
+    Glob,  // [[ == ]] patterns, quoted chars match literally
+    Regex, // [[ =~ ]] patterns, quoted chars match literally
+}
+
+struct Fields {
+    fields: Vec<String>,
+    cur: String,
+    // the current field exists even if empty (e.g. "")
+    active: bool,
+    mode: Mode,
+    // pathname expansion, the current field as a glob pattern with quoted
+    // chars escaped, and whether it has unquoted * ? or [
+    glob: bool,
+    pattern: String,
+    globbing: bool,
+}
+
+impl Fields {
+    fn new(mode: Mode) -> Self {
+        Fields {
+            fields: Vec::new(),
+            cur: String::new(),
+            active: false,
+            glob: mode == Mode::Split && !with_shell(|shell| shell.option("noglob")),
+            mode,
+            pattern: String::new(),
+            globbing: false,
+        }
+    }
+
+    fn push_char(&mut self, ch: char) {
+        self.cur.push(ch);
+        self.active = true;
+        if self.glob {
+            self.pattern.push(ch);
+            self.globbing |= matches!(ch, '*' | '?' | '[');
+        }
+    }
+
+    fn push_str(&mut self, s: &str) {
+        self.active = true;
+        s.chars().for_each(|ch| self.push_char(ch));
+    }
+
+    // quoted chars are escaped when building a pattern
+    fn push_quoted(&mut self, ch: char) {
+        let special = match self.mode {
+            Mode::Split | Mode::Join => false,
+            Mode::Glob => matches!(ch, '*' | '?' | '[' | ']' | '\\'),
+            Mode::Regex => "\\.[](){}*+?|^$".contains(ch),
+        };
+        if special {
+            self.cur.push('\\');
+        }
+        self.cur.push(ch);
+        self.active = true;
+        if self.glob {
+            if matches!(ch, '*' | '?' | '[' | ']' | '\\') {
+                self.pattern.push('\\');
+            }
+            self.pattern.push(ch);
+        }
+    }
+
+    fn push_quoted_str(&mut self, s: &str) {
+        self.active = true;
+        s.chars().for_each(|ch| self.push_quoted(ch));
+    }
+
+    fn delimit(&mut self) {
+        if self.active {
+            self.end_field();
+        }
+    }
+
+    // a field with a glob that matches is replaced by the pathnames
+    fn end_field(&mut self) {
+        let field = mem::take(&mut self.cur);
+        let pattern = mem::take(&mut self.pattern);
+        self.active = false;
+        if mem::take(&mut self.globbing) {
+            let paths = glob_paths(&pattern);
+            if !paths.is_empty() {
+                self.fields.extend(paths);
+                return;
+            }
+        }
+        self.fields.push(field);
+    }
+
+    // unquoted expansion results are split on IFS
+    fn push_split(&mut self, value: &str, ifs: &str) {
+        for ch in value.chars() {
+            if !ifs.contains(ch) {
+                self.push_char(ch);
+            } else if ch.is_whitespace() {
+                self.delimit();
+            } else {
+                self.end_field();
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        self.delimit();
+        self.fields
+    }
+}
+
+pub fn expand_tokens(toks: Vec<Token>) -> Vec<Token> {
+    let mut expanded: Vec<Token> = Vec::new();
+    for tok in toks {
+        match tok {
+            Token::Word(word) => {
+                for field in expand_word(&word) {
+                    expanded.push(Token::Word(field));
+                }
+            }
+            _ => expanded.push(tok),
+        }
+    }
+    expanded
+}
+
+/// Expands parameters in a raw word, splits unquoted results on IFS and
+/// removes quotes. A word may expand to zero or more fields.
+pub fn expand_word(word: &str) -> Vec<String> {
+    expand(word, Mode::Split)
+}
+
+/// Expands a word without field splitting, as done for the value of an
+/// assignment or an array subscript.
+pub fn expand_value(word: &str) -> String {
+    expand(word, Mode::Join).join(" ")
+}
+
+/// Expands a word into a glob pattern for `fnmatch`.
+pub fn expand_glob(word: &str) -> String {
+    expand(word, Mode::Glob).join(" ")
+}
+
+/// Expands a word into an extended regular expression.
+pub fn expand_regex(word: &str) -> String {
+    expand(word, Mode::Regex).join(" ")
+}
+
+fn expand(word: &str, mode: Mode) -> Vec<String> {
+    let chars: Vec<char> = word.chars().collect();
+    let ifs = ifs();
+    let split = mode == Mode::Split;
+    let mut out = Fields::new(mode);
+    let mut in_double = false;
+    // "$@" with no positional parameters expands to nothing
+    let mut quoted_at = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let ch = chars[i];
+        match ch {
+            '\'' if !in_double => {
+                out.active = true;
+                i += 1;
+                while i < chars.len() && chars[i] != '\'' {
+                    out.push_quoted(chars[i]);
+                    i += 1;
+                }
+                i += 1;
+            }
+            '"' => {
+                if in_double && !quoted_at {
+                    out.active = true;
+                }
+                in_double = !in_double;
+                quoted_at = false;
+                i += 1;
+            }
+            '\\' => {
+                i += 1;
+                if i < chars.len() {
+                    let next_ch = chars[i];
+                    if !in_double || matches!(next_ch, '\\' | '"' | '$' | '`') {
+                        out.push_quoted(next_ch);
+                    } else if next_ch != '\n' {
+                        out.push_quoted('\\');
+                        out.push_quoted(next_ch);
+                    }
+                    i += 1;
+                } else {
+                    out.push_char('\\');
+                }
+            }
+            '$' => match parse_param(&chars[i + 1..]) {
+                Some((value, len)) => {
+                    i += len + 1;
+                    match value {
+                        Value::Scalar(s) => {
+                            if in_double {
+                                out.push_quoted_str(&s);
+                            } else if !split {
+                                out.push_str(&s);
+                            } else {
+                                out.push_split(&s, &ifs);
+                            }
+                        }
+                        Value::At(params) if in_double => {
+                            quoted_at = true;
+                            for (j, param) in params.iter().enumerate() {
+                                if j > 0 {
+                                    out.end_field();
+                                }
+                                out.push_quoted_str(param);
+                            }
+                        }
+                        Value::At(params) if !split => {
+                            out.push_str(&params.join(" "));
+                        }
+                        Value::Star(params) if in_double || !split => {
+                            let sep = ifs.chars().next().map(String::from).unwrap_or_default();
+                            if in_double {
+                                out.push_quoted_str(&params.join(&sep));
+                            } else {
+                                out.push_str(&params.join(&sep));
+                            }
+                        }
+                        Value::At(params) | Value::Star(params) => {
+                            for (j, param) in params.iter().enumerate() {
+                                if j > 0 {
+                                    out.delimit();
+                                }
+                                out.push_split(param, &ifs);
+                            }
+                        }
+                    }
+                }
+                None => {
+                    out.push_char('$');
+                    i += 1;
+                }
+            },
+            _ => {
+                if in_double {
+                    out.push_quoted(ch);
+                } else {
+                    out.push_char(ch);
+                }
+                i += 1;
+            }
+        }
+    }
+
+    out.finish()
+}
+
+// returns the value and the number of chars consumed after '$'
+fn parse_param(chars: &[char]) -> Option<(Value, usize)> {
+    let first = *chars.first()?;
+
+    if first == '{' {
+        let end = closing_brace(chars)?;
+        let body: String = chars[1..end].iter().collect();
+        return Some((lookup_braced(&body)?, end + 1));
+    }
+
+    if matches!(first, '@' | '*' | '#' | '?' | '$' | '!' | '-') || first.is_ascii_digit() {
+        return Some((lookup(&first.to_string()), 1));
+    }
+
+    if first.is_ascii_alphabetic() || first == '_' {
+        let len = chars
+            .iter()
+            .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+            .count();
+        let name: String = chars[..len].iter().collect();
+        return Some((lookup(&name), len));
+    }
+
+    None
+}
+
+// the } closing the { at the start, skipping nested ${...}
+fn closing_brace(chars: &[char]) -> Option<usize> {
+    let mut depth = 0;
+    for (i, &c) in chars.iter().enumerate() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// ${name}, ${#name}, ${name[sub]}, ${!name[@]}, ${name[@]:offset:length},
+// ${name:-word} and the other operators, ${name#pattern} and ${name%pattern}
+fn lookup_braced(body: &str) -> Option<Value> {
+    let bad_substitution = || {
+        eprintln!("${{{}}}: bad substitution", body);
+        None
+    };
+
+    let (prefix, rest) = match body.chars().next() {
+        Some(c @ ('#' | '!')) if body.len() > 1 => (Some(c), &body[1..]),
+        _ => (None, body),
+    };
+
+    let name_len = param_name_len(rest);
+    if name_len == 0 {
+        return bad_substitution();
+    }
+    let name = &rest[..name_len];
+    let mut rest = &rest[name_len..];
+
+    let mut subscript = None;
+    if rest.starts_with('[') {
+        let Some(close) = rest.find(']') else {
+            return bad_substitution();
+        };
+        subscript = Some(&rest[1..close]);
+        rest = &rest[close + 1..];
+    }
+
+    if let Some((op, word)) = split_operator(rest) {
+        if prefix.is_some() {
+            return bad_substitution();
+        }
+        return operate(name, subscript, op, word);
+    }
+
+    let range = match rest.strip_prefix(':') {
+        _ if rest.is_empty() => None,
+        Some(range) => match parse_slice(range) {
+            Some(slice) => Some(slice),
+            None => return bad_substitution(),
+        },
+        None => return bad_substitution(),
+    };
+
+    let value = match (prefix, subscript) {
+        (Some('!'), Some("@")) => Value::At(keys(name)),
+        (Some('!'), Some("*")) => Value::Star(keys(name)),
+        (Some('!'), None) => {
+            // ${!name} is the value of the variable named by name
+            let target = match lookup(name) {
+                Value::Scalar(s) => s,
+                _ => return bad_substitution(),
+            };
+            if param_name_len(&target) != target.len() || target.is_empty() {
+                return bad_substitution();
+            }
+            lookup(&target)
+        }
+        (_, subscript) => value_of(name, subscript),
+    };
+
+    let value = match (value, range) {
+        (value, None) => value,
+        (Value::Scalar(s), Some((offset, length))) => {
+            let chars: Vec<char> = s.chars().collect();
+            Value::Scalar(slice(&chars, offset, length).iter().collect())
+        }
+        // ${!name[@]:offset} slices the keys by position
+        (Value::At(items), Some((offset, length))) if prefix.is_some() => {
+            Value::At(slice(&items, offset, length))
+        }
+        (Value::Star(items), Some((offset, length))) if prefix.is_some() => {
+            Value::Star(slice(&items, offset, length))
+        }
+        (Value::At(_), Some((offset, length))) => {
+            Value::At(slice_indexed(&indexed(name), offset, length))
+        }
+        (Value::Star(_), Some((offset, length))) => {
+            Value::Star(slice_indexed(&indexed(name), offset, length))
+        }
+    };
+
+    if prefix == Some('#') {
+        let len = match value {
+            Value::Scalar(s) => s.chars().count(),
+            Value::At(items) | Value::Star(items) => items.len(),
+        };
+        return Some(Value::Scalar(len.to_string()));
+    }
+
+    Some(value)
+}
+
+// the value of name, or of name[subscript]
+fn value_of(name: &str, subscript: Option<&str>) -> Value {
+    match subscript {
+        Some("@") => Value::At(values(name)),
+        Some("*") => Value::Star(values(name)),
+        Some(sub) => Value::Scalar(element(name, &expand_value(sub))),
+        None => lookup(name),
+    }
+}
+
+// the operator after the name and the word following it
+fn split_operator(rest: &str) -> Option<(&str, &str)> {
+    const OPERATORS: [&str; 12] = [
+        ":-", ":=", ":?", ":+", "-", "=", "?", "+", "##", "#", "%%", "%",
+    ];
+    OPERATORS
+        .iter()
+        .find_map(|op| rest.strip_prefix(op).map(|word| (*op, word)))
+}
+
+// ${name-word} uses word when name is unset, = also assigns it, ? fails
+// and + uses word only when name is set; with a colon an empty value counts
+// as unset. # and % remove the shortest matching prefix or suffix, ## and
+// %% the longest.
+fn operate(name: &str, subscript: Option<&str>, op: &str, word: &str) -> Option<Value> {
+    if op.starts_with(['#', '%']) {
+        let pattern = expand_glob(word);
+        let remove = |s: String| remove_pattern(&s, op, &pattern);
+        return Some(match value_of(name, subscript) {
+            Value::Scalar(s) => Value::Scalar(remove(s)),
+            Value::At(items) => Value::At(items.into_iter().map(remove).collect()),
+            Value::Star(items) => Value::Star(items.into_iter().map(remove).collect()),
+        });
+    }
+
+    let value = is_set(name, subscript).then(|| value_of(name, subscript));
+    let unset = match &value {
+        Some(Value::Scalar(s)) => op.starts_with(':') && s.is_empty(),
+        Some(Value::At(items) | Value::Star(items)) => op.starts_with(':') && items.is_empty(),
+        None => true,
+    };
+    match (op.trim_start_matches(':'), value) {
+        ("+", _) if unset => Some(Value::Scalar(String::new())),
+        ("+", _) => Some(Value::Scalar(expand_value(word))),
+        (_, Some(value)) if !unset => Some(value),
+        ("-", _) => Some(Value::Scalar(expand_value(word))),
+        ("=", _) => {
+            let word = expand_value(word);
+            if !is_name(name) {
+                eprintln!("${}: cannot assign in this way", name);
+                with_shell(|shell| shell.expand_failed = true);
+                return None;
+            }
+            let assigned = with_shell(|shell| match subscript {
+                Some(sub) => shell.set_element(name, &expand_value(sub), word.clone()),
+                None => {
+                    shell.set_var(name, word.clone());
+                    Ok(())
+                }
+            });
+            if let Err(e) = assigned {
+                eprintln!("{}", e);
+            }
+            Some(Value::Scalar(word))
+        }
+        _ => {
+            let message = match expand_value(word) {
+                word if word.is_empty() => "parameter null or not set".to_string(),
+                word => word,
+            };
+            with_shell(|shell| {
+                eprintln!("{}: {}: {}", shell.name, name, message);
+                shell.expand_failed = true;
+            });
+            Some(Value::Scalar(String::new()))
+        }
+    }
+}
+
+// whether name, or its element, is set; $@ is set when it has parameters
+fn is_set(name: &str, subscript: Option<&str>) -> bool {
+    let key = match subscript {
+        Some("@" | "*") => return !values(name).is_empty(),
+        Some(sub) => Some(expand_value(sub)),
+        None => None,
+    };
+    with_shell(|shell| match (name, key) {
+        (_, Some(key)) => match shell.vars.get(name) {
+            Some(var) => var.get(&key).is_some(),
+            None => key == "0" && shell.get_var(name).is_some(),
+        },
+        ("@" | "*", None) => !shell.positional.is_empty(),
+        ("!", None) => shell.last_bg_pid.is_some(),
+        ("#" | "?" | "$" | "-" | "0", None) => true,
+        (_, None) => match name.parse::<usize>() {
+            Ok(n) => n >= 1 && n <= shell.positional.len(),
+            Err(_) => shell.get_var(name).is_some(),
+        },
+    })
+}
+
+fn remove_pattern(s: &str, op: &str, pattern: &str) -> String {
+    let prefix = op.starts_with('#');
+    let longest = op.len() == 2;
+    let mut cuts: Vec<usize> = s.char_indices().map(|(i, _)| i).chain([s.len()]).collect();
+    // the shortest prefix ends first, the shortest suffix starts last
+    if prefix == longest {
+        cuts.reverse();
+    }
+    for cut in cuts {
+        let (head, tail) = s.split_at(cut);
+        if prefix && pattern_match(pattern, head) {
+            return tail.to_string();
+        }
+        if !prefix && pattern_match(pattern, tail) {
+            return head.to_string();
+        }
+    }
+    s.to_string()
+}
+
+fn param_name_len(s: &str) -> usize {
+    match s.chars().next() {
+        Some('@' | '*' | '#' | '?' | '$' | '!' | '-') => 1,
+        Some(c) if c.is_ascii_digit() => s.chars().take_while(|c| c.is_ascii_digit()).count(),
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => s
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .count(),
+        _ => 0,
+    }
+}
+
+// offset[:length]
+fn parse_slice(range: &str) -> Option<(i64, Option<i64>)> {
+    let (offset, length) = match range.split_once(':') {
+        Some((offset, length)) => (offset, Some(length)),
+        None => (range, None),
+    };
+    let offset = expand_value(offset).trim().parse::<i64>().ok()?;
+    let length = match length {
+        Some(length) => Some(expand_value(length).trim().parse::<i64>().ok()?),
+        None => None,
+    };
+    Some((offset, length))
+}
+
+fn slice<T: Clone>(items: &[T], offset: i64, length: Option<i64>) -> Vec<T> {
+    let n = items.len() as i64;
+    let start = if offset < 0 {
+        (n + offset).max(0)
+    } else {
+        offset.min(n)
+    };
+    let end = match length {
+        Some(length) if length < 0 => (n + length).max(start),
+        Some(length) => (start + length).min(n),
+        None => n,
+    };
+    items[start as usize..end as usize].to_vec()
+}
+
+// the elements from the one at index offset, or at offset from the end
+// when negative; length counts elements
+fn slice_indexed(items: &[(i64, String)], offset: i64, length: Option<i64>) -> Vec<String> {
+    let end = items.last().map_or(0, |(index, _)| index + 1);
+    let first = if offset < 0 { end + offset } else { offset };
+    if first < 0 {
+        return Vec::new();
+    }
+    let values: Vec<String> = items
+        .iter()
+        .filter(|(index, _)| *index >= first)
+        .map(|(_, value)| value.clone())
+        .collect();
+    slice(&values, 0, length)
+}
+
+// the elements with their indices, $0 comes first in $@ when slicing
+fn indexed(name: &str) -> Vec<(i64, String)> {
+    with_shell(|shell| {
+        let values = match name {
+            "@" | "*" => [shell.name.clone()]
+                .into_iter()
+                .chain(shell.positional.iter().cloned())
+                .collect(),
+            _ => match shell.vars.get(name) {
+                Some(Var::Indexed(arr)) => {
+                    return arr.iter().map(|(i, value)| (*i, value.clone())).collect();
+                }
+                Some(var) => var.values(),
+                None => shell.get_var(name).into_iter().collect(),
+            },
+        };
+        (0..).zip(values).collect()
+    })
+}
+
+fn values(name: &str) -> Vec<String> {
+    with_shell(|shell| match name {
+        "@" | "*" => shell.positional.clone(),
+        _ => match shell.vars.get(name) {
+            Some(var) => var.values(),
+            None => shell.get_var(name).into_iter().collect(),
+        },
+    })
+}
+
+fn keys(name: &str) -> Vec<String> {
+    with_shell(|shell| match name {
+        "@" | "*" => (1..=shell.positional.len())
+            .map(|i| i.to_string())
+            .collect(),
+        _ => match shell.vars.get(name) {
+            Some(var) => var.keys(),
+            None => shell
+                .get_var(name)
+                .map(|_| "0".to_string())
+                .into_iter()
+                .collect(),
+        },
+    })
+}
+
+fn element(name: &str, key: &str) -> String {
+    with_shell(|shell| {
+        let value = match shell.vars.get(name) {
+            Some(var) => var.get(key),
+            None if key == "0" => shell.get_var(name),
+            None => None,
+        };
+        if value.is_none() {
+            unbound(shell, &format!("{}[{}]", name, key));
+        }
+        value.unwrap_or_default()
+    })
+}
+
+fn lookup(name: &str) -> Value {
+    with_shell(|shell| match name {
+        "@" => Value::At(shell.positional.clone()),
+        "*" => Value::Star(shell.positional.clone()),
+        "#" => Value::Scalar(shell.positional.len().to_string()),
+        "?" => Value::Scalar(shell.last_status.to_string()),
+        "$" => Value::Scalar(shell.pid.to_string()),
+        "!" => Value::Scalar(
+            shell
+                .last_bg_pid
+                .map(|pid| pid.to_string())
+                .unwrap_or_default(),
+        ),
+        "-" => Value::Scalar(shell.flags()),
+        "0" => Value::Scalar(shell.name.clone()),
+        _ => {
+            let value = if let Ok(n) = name.parse::<usize>() {
+                n.checked_sub(1)
+                    .and_then(|n| shell.positional.get(n))
+                    .cloned()
+            } else {
+                shell.get_var(name)
+            };
+            if value.is_none() {
+                unbound(shell, name);
+            }
+            Value::Scalar(value.unwrap_or_default())
+        }
+    })
+}
+
+// set -u makes expanding an unset variable an error
+fn unbound(shell: &mut Shell, name: &str) {
+    if shell.option("nounset") {
+        eprintln!("{}: {}: unbound variable", shell.name, name);
+        shell.expand_failed = true;
+    }
+}
+
+fn ifs() -> String {
+    with_shell(|shell| shell.get_var("IFS")).unwrap_or_else(|| " \t\n".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(name: &str, value: &str) {
+        with_shell(|shell| shell.set_var(name, value.to_string()));
+    }
+
+    fn set_positional(params: &[&str]) {
+        with_shell(|shell| shell.positional = params.iter().map(|p| p.to_string()).collect());
+    }
+
+    #[test]
+    fn defaults_and_alternatives() {
+        set("empty", "");
+        assert_eq!(expand_value("${empty:-x}"), "x");
+        assert_eq!(expand_value("${empty-x}"), "");
+        assert_eq!(expand_value("${no_such_var-x}"), "x");
+        assert_eq!(expand_value("${empty:+y}"), "");
+        assert_eq!(expand_value("${empty+y}"), "y");
+        assert_eq!(expand_value("${no_such_var:-$empty}"), "");
+
+        assert_eq!(expand_value("${assigned:=z}"), "z");
+        assert_eq!(
+            with_shell(|shell| shell.get_var("assigned")).as_deref(),
+            Some("z")
+        );
+        assert_eq!(expand_value("${assigned:=other}"), "z");
+    }
+
+    #[test]
+    fn unset_is_an_error_with_question_mark() {
+        with_shell(|shell| shell.options.insert("nounset", true));
+        assert_eq!(expand_value("${no_such_var:-ok}"), "ok");
+        assert!(!with_shell(|shell| shell.expand_failed));
+
+        set("empty", "");
+        assert_eq!(expand_value("${empty?}"), "");
+        assert!(!with_shell(|shell| shell.expand_failed));
+        assert_eq!(expand_value("${empty:?}"), "");
+        assert!(with_shell(|shell| mem::take(&mut shell.expand_failed)));
+    }
+
+    #[test]
+    fn lengths() {
+        set("word", "héllo");
+        set_positional(&["a", "b", "c"]);
+        assert_eq!(expand_value("${#word}"), "5");
+        assert_eq!(expand_value("${#no_such_var}"), "0");
+        assert_eq!(expand_value("$# ${#@}"), "3 3");
+    }
+
+    #[test]
+    fn pattern_removal() {
+        set("path", "/usr/lib/file.tar.gz");
+        assert_eq!(expand_value("${path#*/}"), "usr/lib/file.tar.gz");
+        assert_eq!(expand_value("${path##*/}"), "file.tar.gz");
+        assert_eq!(expand_value("${path%.*}"), "/usr/lib/file.tar");
+        assert_eq!(expand_value("${path%%.*}"), "/usr/lib/file");
+        assert_eq!(expand_value("${path%'.*'}"), "/usr/lib/file.tar.gz");
+        assert_eq!(expand_value("${path#nomatch}"), "/usr/lib/file.tar.gz");
+        set_positional(&["a.c", "b.c"]);
+        assert_eq!(expand_word("${@%.c}"), ["a", "b"]);
+    }
+
+    #[test]
+    fn quoted_at_and_star() {
+        set_positional(&["a b", "c"]);
+        assert_eq!(expand_word("\"$@\""), ["a b", "c"]);
+        assert_eq!(expand_word("\"x$@y\""), ["xa b", "cy"]);
+        assert_eq!(expand_word("\"$*\""), ["a b c"]);
+        assert_eq!(expand_word("$@"), ["a", "b", "c"]);
+        assert_eq!(expand_word("$*"), ["a", "b", "c"]);
+        set("IFS", ":");
+        assert_eq!(expand_word("\"$*\""), ["a b:c"]);
+
+        set_positional(&[]);
+        assert!(expand_word("\"$@\"").is_empty());
+        assert_eq!(expand_word("\"$*\""), [""]);
+    }
+
+    #[test]
+    fn ifs_splitting() {
+        set("spaced", " a  b ");
+        assert_eq!(expand_word("$spaced"), ["a", "b"]);
+        assert_eq!(expand_word("\"$spaced\""), [" a  b "]);
+        set("IFS", ",");
+        set("commas", "a,,b");
+        assert_eq!(expand_word("$commas"), ["a", "", "b"]);
+    }
+
+    #[test]
+    fn empty_ifs_doesnt_split() {
+        set("IFS", "");
+        set("spaced", "a b");
+        set_positional(&["c d", "e"]);
+        assert_eq!(expand_word("$spaced"), ["a b"]);
+        assert_eq!(expand_word("$@"), ["c d", "e"]);
+        assert_eq!(expand_word("\"$*\""), ["c de"]);
+    }
+
+    #[test]
+    fn slices_start_at_an_index() {
+        with_shell(|shell| shell.name = "sh".to_string());
+        set_positional(&["a", "b", "c"]);
+        assert_eq!(expand_word("${@:2}"), ["b", "c"]);
+        assert_eq!(expand_word("${@:0:2}"), ["sh", "a"]);
+        assert_eq!(expand_word("${*: -1}"), ["c"]);
+        assert!(expand_word("${@: -5}").is_empty());
+
+        set("word", "hello");
+        assert_eq!(expand_value("${word:1:3} ${word: -2}"), "ell lo");
+
+        with_shell(|shell| {
+            shell.set_array(
+                "sparse",
+                vec![
+                    (Some("2".to_string()), "x".to_string()),
+                    (Some("5".to_string()), "a".to_string()),
+                    (None, "b".to_string()),
+                ],
+            )
+        })
+        .unwrap();
+        assert_eq!(expand_word("${sparse[@]:1:2}"), ["x", "a"]);
+        assert_eq!(expand_word("${sparse[@]:3}"), ["a", "b"]);
+        assert_eq!(expand_word("${sparse[@]: -2}"), ["a", "b"]);
+    }
+}
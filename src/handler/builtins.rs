@@ -1,84 +1,1553 @@
 use crate::{
-    handler::cmds::{Cmd, get_builtins},
-    utils::check_ext_cmd,
+    confirm_exit, exit_shell,
+    handler::{
+        cmds::{Cmd, get_builtins},
+        cond::{binary_test, is_unary_op, unary_test},
+        printf::{expand_escapes, printf},
+        read::read,
+    },
+    jobs::{self, JobState, format_state},
+    run_script,
+    signals::{SIGNALS, signal_name, signal_number},
+    state::{Var, option_name, with_shell},
+    trap::{self, PSEUDO_SIGNALS, set_trap, trap_name},
+    utils::{check_ext_cmd, strip_os_error},
+};
+use std::{
+    env, fs,
+    os::unix::fs::MetadataExt,
+    path::{Component, Path, PathBuf},
 };
-use std::{env, path::Path, process::exit};
 
 pub fn handle_builtins(cmd: &mut Cmd) {
     match cmd.name.as_str() {
-        "exit" => exit(0),
+        "exit" | "logout" => exit(cmd),
+        "shopt" => shopt(cmd),
         "echo" => echo(cmd),
         "type" => types(cmd),
         "pwd" => pwd(cmd),
         "cd" => cd(cmd),
+        "set" => set(cmd),
+        "shift" => shift(cmd),
+        "declare" => declare(cmd),
+        "test" | "[" => test(cmd),
+        "jobs" => list_jobs(cmd),
+        "fg" => fg(cmd),
+        "bg" => bg(cmd),
+        "wait" => wait(cmd),
+        "kill" => kill(cmd),
+        "disown" => disown(cmd),
+        "source" | "." => source(cmd),
+        "return" => return_builtin(cmd),
+        "trap" => trap(cmd),
+        "ulimit" => ulimit(cmd),
+        "umask" => umask(cmd),
+        "read" => read(cmd),
+        "printf" => printf(cmd),
         _ => eprintln!("Unknown builtins"),
     }
 }
 
 pub fn is_builtin(cmd: String) -> bool {
     let builtins: Vec<&'static str> = get_builtins();
-    if builtins.contains(&cmd.as_str()) {
-        true
-    } else {
-        false
-    }
+    builtins.contains(&cmd.as_str())
 }
 
+// echo [-neE] [arg ...], -e expands backslash escapes and -E doesn't,
+// `shopt -s xpg_echo` makes -e the default
 fn echo(cmd: &mut Cmd) {
     cmd.name = "echo".to_string();
-    if cmd.args.is_empty() {
-        cmd.stdout = "\n".to_string();
+    let mut newline = true;
+    let mut escapes = with_shell(|shell| shell.shopts["xpg_echo"]);
+
+    // only words made of option letters are options
+    let mut words = cmd.args.as_slice();
+    while let Some(flags) = words.first().and_then(|word| word.strip_prefix('-'))
+        && !flags.is_empty()
+        && flags.chars().all(|flag| matches!(flag, 'n' | 'e' | 'E'))
+    {
+        for flag in flags.chars() {
+            match flag {
+                'n' => newline = false,
+                'e' => escapes = true,
+                _ => escapes = false,
+            }
+        }
+        words = &words[1..];
     }
-    cmd.stdout = cmd.args.join(" ") + "\n";
+
+    let text = words.join(" ");
+    let mut out = if escapes {
+        let (bytes, stop) = expand_escapes(&text);
+        // \c also drops the newline
+        newline &= !stop;
+        bytes
+    } else {
+        text.into_bytes()
+    };
+    if newline {
+        out.push(b'\n');
+    }
+    cmd.raw_stdout = out;
 }
 
+// words the shell parses itself instead of running them
+const KEYWORDS: [&str; 3] = ["[[", "]]", "time"];
+
 fn types(cmd: &mut Cmd) {
     cmd.name = "type".to_string();
-    if let Some(exec) = cmd.args.first().clone() {
-        if is_builtin(exec.to_string()) {
+    if let Some(exec) = cmd.args.first() {
+        if KEYWORDS.contains(&exec.as_str()) {
+            cmd.stdout = format!("{} is a shell keyword\n", exec);
+        } else if is_builtin(exec.to_string()) {
             cmd.stdout = format!("{} is a shell builtin\n", exec);
         } else {
-            let (found, full_path) = check_ext_cmd(&exec);
+            let (found, full_path) = check_ext_cmd(exec);
             if found {
                 cmd.stdout = format!("{} is {}\n", exec, full_path.unwrap().display());
             } else {
                 cmd.stderr = format!("{} not found\n", exec);
+                cmd.status = 1;
             }
         }
     }
 }
 
+// pwd [-LP], the logical directory kept in PWD unless -P asks for the one
+// with symlinks resolved
 fn pwd(cmd: &mut Cmd) {
     cmd.name = "pwd".to_string();
-    match env::current_dir() {
+    let physical = cmd
+        .args
+        .iter()
+        .rfind(|arg| matches!(arg.as_str(), "-L" | "-P"))
+        .is_some_and(|arg| arg == "-P");
+    let dir = if physical {
+        env::current_dir()
+    } else {
+        logical_pwd()
+    };
+    match dir {
         Ok(path) => {
             cmd.stdout = path.display().to_string() + "\n";
         }
         Err(e) => {
-            cmd.stderr = format!("Failed to cwd: {}\n", e);
+            cmd.stderr = format!(
+                "pwd: error retrieving current directory: {}\n",
+                strip_os_error(&e)
+            );
+            cmd.status = 1;
         }
     }
 }
 
+// PWD when it still names the current directory
+fn logical_pwd() -> std::io::Result<PathBuf> {
+    let cwd = env::current_dir()?;
+    let pwd = with_shell(|shell| shell.get_var("PWD")).map(PathBuf::from);
+    let same = |pwd: &Path| match (fs::metadata(pwd), fs::metadata(&cwd)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    };
+    match pwd {
+        Some(pwd) if pwd.is_absolute() && same(&pwd) => Ok(pwd),
+        _ => Ok(cwd),
+    }
+}
+
+// cd [-L|-P] [dir], HOME without a dir and OLDPWD for -; a relative dir
+// is looked up in CDPATH. -L (the default) keeps the path as typed, with
+// .. removing the previous component, -P resolves symlinks.
 fn cd(cmd: &mut Cmd) {
     cmd.name = "cd".to_string();
-    if let Some(dir) = cmd.args.first() {
-        if dir == "~" {
-            match env::var("HOME") {
-                Ok(val) => {
-                    env::set_current_dir(val).expect("Failed to change dir");
+    let mut physical = false;
+    let mut operands = Vec::new();
+    let mut args = cmd.args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-L" => physical = false,
+            "-P" => physical = true,
+            "--" => {
+                operands.extend(args.by_ref());
+            }
+            _ if arg.len() > 1 && arg.starts_with('-') => {
+                cmd.stderr = format!("cd: {}: invalid option\n", arg);
+                cmd.status = 2;
+                return;
+            }
+            _ => operands.push(arg),
+        }
+    }
+    if operands.len() > 1 {
+        cmd.stderr = "cd: too many arguments\n".to_string();
+        cmd.status = 1;
+        return;
+    }
+
+    // CDPATH=dir cd ... looks in the dirs given to the command
+    let env = cmd.env.clone();
+    let var = |name: &str| {
+        env.iter()
+            .rfind(|(var, _)| var == name)
+            .map(|(_, value)| value.clone())
+            .or_else(|| with_shell(|shell| shell.get_var(name)))
+            .filter(|val| !val.is_empty())
+    };
+    let mut print = false;
+    let dir = match operands.first().map(|dir| dir.as_str()) {
+        None => match var("HOME") {
+            Some(home) => home,
+            None => {
+                cmd.stderr = "cd: HOME not set\n".to_string();
+                cmd.status = 1;
+                return;
+            }
+        },
+        Some("-") => match var("OLDPWD") {
+            Some(oldpwd) => {
+                print = true;
+                oldpwd
+            }
+            None => {
+                cmd.stderr = "cd: OLDPWD not set\n".to_string();
+                cmd.status = 1;
+                return;
+            }
+        },
+        Some(dir) => match (dir.strip_prefix('~'), var("HOME")) {
+            (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+                format!("{}{}", home, rest)
+            }
+            _ => dir.to_string(),
+        },
+    };
+
+    // CDPATH isn't used for dir/, ./dir or ../dir, and a directory found
+    // through it is printed
+    let mut target = PathBuf::from(&dir);
+    let first = Path::new(&dir).components().next();
+    if !matches!(
+        first,
+        None | Some(Component::RootDir | Component::CurDir | Component::ParentDir)
+    ) && let Some(cdpath) = var("CDPATH")
+    {
+        for entry in cdpath.split(':') {
+            let candidate = Path::new(if entry.is_empty() { "." } else { entry }).join(&dir);
+            if candidate.is_dir() {
+                print |= !entry.is_empty();
+                target = candidate;
+                break;
+            }
+        }
+    }
+
+    let oldpwd = logical_pwd().ok();
+    let target = match (&oldpwd, physical) {
+        (Some(pwd), false) => normalize(&pwd.join(&target)),
+        _ => target,
+    };
+    if let Err(e) = env::set_current_dir(&target) {
+        cmd.stderr = format!("cd: {}: {}\n", dir, strip_os_error(&e));
+        cmd.status = 1;
+        return;
+    }
+
+    let pwd = if physical {
+        env::current_dir().unwrap_or(target)
+    } else {
+        target
+    };
+    if print {
+        cmd.stdout = format!("{}\n", pwd.display());
+    }
+    with_shell(|shell| {
+        if let Some(oldpwd) = oldpwd {
+            shell.set_var("OLDPWD", oldpwd.display().to_string());
+        }
+        shell.set_var("PWD", pwd.display().to_string());
+    });
+}
+
+// /a/./b/../c is /a/c, without looking at symlinks
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normal.pop();
+            }
+            Component::Normal(name) => normal.push(name),
+            _ => {}
+        }
+    }
+    normal
+}
+
+// set [-euxCfn] [-o option] [--] [args]: -flag turns an option on, +flag
+// off, the remaining args replace the positional parameters
+fn set(cmd: &mut Cmd) {
+    cmd.name = "set".to_string();
+    let args = cmd.args.clone();
+    let mut positional = None;
+    let mut i = 0;
+
+    while i < args.len() {
+        let arg = &args[i];
+        if arg == "--" {
+            positional = Some(args[i + 1..].to_vec());
+            break;
+        }
+        // a lone - turns tracing off and ends the options, keeping the
+        // positional parameters unless words follow
+        if arg == "-" {
+            with_shell(|shell| shell.set_option("xtrace", false)).ok();
+            if i + 1 < args.len() {
+                positional = Some(args[i + 1..].to_vec());
+            }
+            break;
+        }
+        if arg.len() < 2 || !(arg.starts_with('-') || arg.starts_with('+')) {
+            positional = Some(args[i..].to_vec());
+            break;
+        }
+
+        let on = arg.starts_with('-');
+        for flag in arg[1..].chars() {
+            let name = if flag == 'o' {
+                i += 1;
+                match args.get(i) {
+                    Some(name) => name.clone(),
+                    None => {
+                        list_options(cmd, on);
+                        continue;
+                    }
+                }
+            } else {
+                match option_name(flag) {
+                    Some(name) => name.to_string(),
+                    None => {
+                        cmd.stderr += &format!("set: {}{}: invalid option\n", &arg[..1], flag);
+                        cmd.status = 2;
+                        return;
+                    }
+                }
+            };
+            if let Err(e) = with_shell(|shell| shell.set_option(&name, on)) {
+                cmd.stderr += &format!("set: {}\n", e);
+                cmd.status = 1;
+                return;
+            }
+        }
+        i += 1;
+    }
+
+    if let Some(args) = positional {
+        with_shell(|shell| shell.positional = args);
+    }
+}
+
+// set -o lists the options, set +o prints the commands that restore them
+fn list_options(cmd: &mut Cmd, on: bool) {
+    with_shell(|shell| {
+        for (name, value) in &shell.options {
+            cmd.stdout += &match (on, value) {
+                (true, true) => format!("{:<15}\ton\n", name),
+                (true, false) => format!("{:<15}\toff\n", name),
+                (false, true) => format!("set -o {}\n", name),
+                (false, false) => format!("set +o {}\n", name),
+            };
+        }
+    });
+}
+
+fn shift(cmd: &mut Cmd) {
+    cmd.name = "shift".to_string();
+    let n = match cmd.args.first() {
+        Some(arg) => match arg.parse::<usize>() {
+            Ok(n) => n,
+            Err(_) => {
+                cmd.stderr = format!("shift: {}: numeric argument required\n", arg);
+                cmd.status = 1;
+                return;
+            }
+        },
+        None => 1,
+    };
+
+    let shifted = with_shell(|shell| {
+        if n > shell.positional.len() {
+            return false;
+        }
+        shell.positional.drain(..n);
+        true
+    });
+    if !shifted {
+        cmd.stderr = format!("shift: {}: shift count out of range\n", n);
+        cmd.status = 1;
+    }
+}
+
+fn declare(cmd: &mut Cmd) {
+    cmd.name = "declare".to_string();
+    let mut indexed = false;
+    let mut assoc = false;
+    let mut print = false;
+    let mut names: Vec<String> = Vec::new();
+
+    for arg in &cmd.args {
+        match arg.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() && names.is_empty() => {
+                for flag in flags.chars() {
+                    match flag {
+                        'a' => indexed = true,
+                        'A' => assoc = true,
+                        'p' => print = true,
+                        _ => {
+                            cmd.stderr = format!("declare: -{}: invalid option\n", flag);
+                            cmd.status = 2;
+                            return;
+                        }
+                    }
+                }
+            }
+            _ => names.push(arg.clone()),
+        }
+    }
+
+    if print || (names.is_empty() && !indexed && !assoc) {
+        if names.is_empty() {
+            names = with_shell(|shell| shell.vars.keys().cloned().collect());
+            names.sort();
+        }
+        for name in &names {
+            match with_shell(|shell| shell.vars.get(name).cloned()) {
+                Some(var) => cmd.stdout += &format_declare(name, &var),
+                None => {
+                    cmd.stderr += &format!("declare: {}: not found\n", name);
+                    cmd.status = 1;
+                }
+            }
+        }
+        return;
+    }
+
+    if indexed || assoc {
+        for name in &names {
+            if let Err(e) = with_shell(|shell| shell.declare_array(name, assoc)) {
+                cmd.stderr += &format!("declare: {}\n", e);
+                cmd.status = 1;
+            }
+        }
+    }
+}
+
+fn format_declare(name: &str, var: &Var) -> String {
+    let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+    match var {
+        Var::Scalar(s) => format!("declare -- {}={}\n", name, quote(s)),
+        Var::Indexed(_) | Var::Assoc(_) => {
+            let flag = if matches!(var, Var::Assoc(_)) {
+                "-A"
+            } else {
+                "-a"
+            };
+            let elements: Vec<String> = var
+                .keys()
+                .iter()
+                .zip(var.values())
+                .map(|(key, value)| format!("[{}]={}", key, quote(&value)))
+                .collect();
+            format!("declare {} {}=({})\n", flag, name, elements.join(" "))
+        }
+    }
+}
+
+fn test(cmd: &mut Cmd) {
+    let name = cmd.name.clone();
+    let mut args = cmd.args.clone();
+    if name == "[" {
+        if args.last().map(String::as_str) != Some("]") {
+            cmd.stderr = "[: missing `]'\n".to_string();
+            cmd.status = 2;
+            return;
+        }
+        args.pop();
+    }
+
+    let result = match args.len() {
+        0 => Ok(false),
+        1 => Ok(!args[0].is_empty()),
+        _ => {
+            let mut parser = TestParser {
+                args: &args,
+                pos: 0,
+            };
+            parser
+                .parse_or()
+                .and_then(|res| match parser.args.get(parser.pos) {
+                    Some(arg) => Err(format!("{}: unexpected argument", arg)),
+                    None => Ok(res),
+                })
+        }
+    };
+
+    match result {
+        Ok(res) => cmd.status = if res { 0 } else { 1 },
+        Err(e) => {
+            cmd.stderr = format!("{}: {}\n", name, e);
+            cmd.status = 2;
+        }
+    }
+}
+
+struct TestParser<'a> {
+    args: &'a [String],
+    pos: usize,
+}
+
+impl TestParser<'_> {
+    fn peek(&self, offset: usize) -> Option<&str> {
+        self.args.get(self.pos + offset).map(String::as_str)
+    }
+
+    fn parse_or(&mut self) -> Result<bool, String> {
+        let mut res = self.parse_and()?;
+        while self.peek(0) == Some("-o") {
+            self.pos += 1;
+            res = self.parse_and()? || res;
+        }
+        Ok(res)
+    }
+
+    fn parse_and(&mut self) -> Result<bool, String> {
+        let mut res = self.parse_not()?;
+        while self.peek(0) == Some("-a") {
+            self.pos += 1;
+            res = self.parse_not()? && res;
+        }
+        Ok(res)
+    }
+
+    fn parse_not(&mut self) -> Result<bool, String> {
+        // ! followed by a binary operator is a string operand: [ ! = x ]
+        if self.peek(0) == Some("!") && !self.peek(1).is_some_and(is_test_binary_op) {
+            self.pos += 1;
+            return Ok(!self.parse_not()?);
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<bool, String> {
+        let Some(first) = self.peek(0).map(String::from) else {
+            return Err("argument expected".to_string());
+        };
+
+        if let (Some(op), Some(rhs)) = (self.peek(1), self.peek(2))
+            && is_test_binary_op(op)
+        {
+            let (op, rhs) = (op.to_string(), rhs.to_string());
+            self.pos += 3;
+            return binary_test(&op, &first, &rhs);
+        }
+
+        if first == "(" && self.peek(1) != Some(")") {
+            self.pos += 1;
+            let res = self.parse_or()?;
+            if self.peek(0) != Some(")") {
+                return Err("`)' expected".to_string());
+            }
+            self.pos += 1;
+            return Ok(res);
+        }
+
+        if is_unary_op(&first) {
+            return match self.peek(1) {
+                Some(operand) => {
+                    let operand = operand.to_string();
+                    self.pos += 2;
+                    Ok(unary_test(&first, &operand))
+                }
+                // a lone operator is just a non-empty string
+                None => {
+                    self.pos += 1;
+                    Ok(true)
+                }
+            };
+        }
+
+        if first.starts_with('-') && first.len() > 1 && self.peek(1).is_some() {
+            return Err(format!("{}: unary operator expected", first));
+        }
+
+        self.pos += 1;
+        Ok(!first.is_empty())
+    }
+}
+
+fn is_test_binary_op(op: &str) -> bool {
+    matches!(
+        op,
+        "=" | "=="
+            | "!="
+            | "<"
+            | ">"
+            | "-eq"
+            | "-ne"
+            | "-lt"
+            | "-le"
+            | "-gt"
+            | "-ge"
+            | "-nt"
+            | "-ot"
+            | "-ef"
+    )
+}
+
+fn list_jobs(cmd: &mut Cmd) {
+    cmd.name = "jobs".to_string();
+    let mut long = false;
+    let mut pids_only = false;
+    let mut specs: Vec<String> = Vec::new();
+    for arg in &cmd.args {
+        match arg.as_str() {
+            "-l" => long = true,
+            "-p" => pids_only = true,
+            _ => specs.push(arg.clone()),
+        }
+    }
+
+    jobs::reap();
+    let ids: Vec<usize> = if specs.is_empty() {
+        with_shell(|shell| shell.jobs.jobs.iter().map(|job| job.id).collect())
+    } else {
+        let mut ids = Vec::new();
+        for spec in &specs {
+            match with_shell(|shell| shell.jobs.resolve(Some(spec))) {
+                Ok(id) => ids.push(id),
+                Err(e) => {
+                    cmd.stderr += &format!("jobs: {}\n", e);
+                    cmd.status = 1;
+                }
+            }
+        }
+        ids
+    };
+
+    for id in ids {
+        let Some((job, marker)) = with_shell(|shell| {
+            let marker = shell.jobs.marker(id);
+            shell.jobs.get(id).map(|job| (job.clone(), marker))
+        }) else {
+            continue;
+        };
+
+        if pids_only {
+            cmd.stdout += &format!("{}\n", job.pgid);
+            continue;
+        }
+
+        let state = job.state();
+        let suffix = if state == JobState::Running { " &" } else { "" };
+        if long {
+            cmd.stdout += &format!(
+                "[{}]{} {} {:<24}{}{}\n",
+                job.id,
+                marker,
+                job.pgid,
+                format_state(state),
+                job.cmd,
+                suffix
+            );
+        } else {
+            cmd.stdout += &format!(
+                "[{}]{}  {:<24}{}{}\n",
+                job.id,
+                marker,
+                format_state(state),
+                job.cmd,
+                suffix
+            );
+        }
+    }
+}
+
+fn fg(cmd: &mut Cmd) {
+    cmd.name = "fg".to_string();
+    if !with_shell(|shell| shell.job_control) {
+        cmd.stderr = "fg: no job control\n".to_string();
+        cmd.status = 1;
+        return;
+    }
+
+    match with_shell(|shell| shell.jobs.resolve(cmd.args.first().map(String::as_str))) {
+        Ok(id) => cmd.status = jobs::foreground(id),
+        Err(e) => {
+            cmd.stderr = format!("fg: {}\n", e);
+            cmd.status = 1;
+        }
+    }
+}
+
+fn bg(cmd: &mut Cmd) {
+    cmd.name = "bg".to_string();
+    if !with_shell(|shell| shell.job_control) {
+        cmd.stderr = "bg: no job control\n".to_string();
+        cmd.status = 1;
+        return;
+    }
+
+    let specs: Vec<Option<&str>> = if cmd.args.is_empty() {
+        vec![None]
+    } else {
+        cmd.args.iter().map(|arg| Some(arg.as_str())).collect()
+    };
+    for spec in specs {
+        let result = with_shell(|shell| shell.jobs.resolve(spec)).and_then(jobs::background);
+        match result {
+            Ok(line) => cmd.stdout += &line,
+            Err(e) => {
+                cmd.stderr += &format!("bg: {}\n", e);
+                cmd.status = 1;
+            }
+        }
+    }
+}
+
+fn wait(cmd: &mut Cmd) {
+    cmd.name = "wait".to_string();
+    let mut next = false;
+    let mut specs: Vec<String> = Vec::new();
+    for arg in &cmd.args {
+        match arg.as_str() {
+            "-n" => next = true,
+            _ => specs.push(arg.clone()),
+        }
+    }
+
+    jobs::reap();
+    if next {
+        cmd.status = jobs::wait_any().unwrap_or(127);
+        return;
+    }
+
+    if specs.is_empty() {
+        let ids: Vec<usize> = with_shell(|shell| {
+            shell
+                .jobs
+                .jobs
+                .iter()
+                .filter(|job| job.state() != JobState::Stopped)
+                .map(|job| job.id)
+                .collect()
+        });
+        for id in ids {
+            jobs::wait_job(id);
+        }
+        cmd.status = 0;
+        return;
+    }
+
+    for spec in &specs {
+        match with_shell(|shell| shell.jobs.resolve(Some(spec))) {
+            Ok(id) => cmd.status = jobs::wait_job(id),
+            Err(e) => {
+                // the job may already have been reported as done
+                let finished = spec
+                    .parse::<i32>()
+                    .ok()
+                    .and_then(|pid| with_shell(|shell| shell.jobs.finished.get(&pid).copied()));
+                match finished {
+                    Some(code) => cmd.status = code,
+                    None if spec.starts_with('%') => {
+                        cmd.stderr += &format!("wait: {}\n", e);
+                        cmd.status = 127;
+                    }
+                    None => {
+                        cmd.stderr += &format!("wait: pid {} is not a child of this shell\n", spec);
+                        cmd.status = 127;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn kill(cmd: &mut Cmd) {
+    cmd.name = "kill".to_string();
+    let usage = "kill: usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... or kill -l [sigspec]\n";
+    let args = cmd.args.clone();
+
+    if matches!(args.first().map(String::as_str), Some("-l" | "-L")) {
+        if args.len() == 1 {
+            let names: Vec<&str> = SIGNALS.iter().map(|(name, _)| *name).collect();
+            cmd.stdout = names.join(" ") + "\n";
+            return;
+        }
+        for arg in &args[1..] {
+            // numbers (or exit statuses above 128) give names and back
+            let line = match arg.parse::<i32>() {
+                Ok(num) => signal_name(if num > 128 { num - 128 } else { num }).map(String::from),
+                Err(_) => signal_number(arg).map(|num| num.to_string()),
+            };
+            match line {
+                Some(line) => cmd.stdout += &format!("{}\n", line),
+                None => {
+                    cmd.stderr += &format!("kill: {}: invalid signal specification\n", arg);
+                    cmd.status = 1;
+                }
+            }
+        }
+        return;
+    }
+
+    let mut sig = libc::SIGTERM;
+    let mut targets = &args[..];
+    match args.first().map(String::as_str) {
+        Some("-s" | "-n") => {
+            let Some(spec) = args.get(1) else {
+                cmd.stderr = usage.to_string();
+                cmd.status = 2;
+                return;
+            };
+            match signal_number(spec) {
+                Some(num) => sig = num,
+                None => {
+                    cmd.stderr = format!("kill: {}: invalid signal specification\n", spec);
+                    cmd.status = 1;
+                    return;
+                }
+            }
+            targets = &args[2..];
+        }
+        Some("--") => targets = &args[1..],
+        Some(arg) if arg.starts_with('-') && arg.len() > 1 => {
+            match signal_number(&arg[1..]) {
+                Some(num) => sig = num,
+                None => {
+                    cmd.stderr = format!("kill: {}: invalid signal specification\n", &arg[1..]);
+                    cmd.status = 1;
+                    return;
+                }
+            }
+            targets = &args[1..];
+        }
+        _ => {}
+    }
+
+    if targets.is_empty() {
+        cmd.stderr = usage.to_string();
+        cmd.status = 2;
+        return;
+    }
+
+    for target in targets {
+        let pid = if target.starts_with('%') {
+            match with_shell(|shell| {
+                let id = shell.jobs.resolve(Some(target))?;
+                let job = shell.jobs.get(id).unwrap();
+                Ok::<_, String>((job.pgid, job.state() == JobState::Stopped))
+            }) {
+                Ok((pgid, stopped)) => {
+                    // every job has its own process group
+                    let pid = -pgid;
+                    // a stopped job has to be continued to see the signal
+                    if stopped && sig != libc::SIGCONT {
+                        unsafe { libc::kill(pid, libc::SIGCONT) };
+                    }
+                    pid
                 }
                 Err(e) => {
-                    cmd.stderr = format!("{}", e);
+                    cmd.stderr += &format!("kill: {}\n", e);
+                    cmd.status = 1;
+                    continue;
                 }
             }
         } else {
-            let path: &Path = Path::new(dir);
-            if path.exists() {
-                env::set_current_dir(dir).expect("Failed to change dir");
+            match target.parse::<i32>() {
+                Ok(pid) => pid,
+                Err(_) => {
+                    cmd.stderr +=
+                        &format!("kill: {}: arguments must be process or job IDs\n", target);
+                    cmd.status = 1;
+                    continue;
+                }
+            }
+        };
+
+        if unsafe { libc::kill(pid, sig) } < 0 {
+            let err = std::io::Error::last_os_error();
+            cmd.stderr += &format!("kill: ({}) - {}\n", target, strip_os_error(&err));
+            cmd.status = 1;
+        }
+    }
+}
+
+fn disown(cmd: &mut Cmd) {
+    cmd.name = "disown".to_string();
+    let mut keep = false;
+    let mut all = false;
+    let mut running = false;
+    let mut specs: Vec<String> = Vec::new();
+    for arg in &cmd.args {
+        match arg.as_str() {
+            "-h" => keep = true,
+            "-a" => all = true,
+            "-r" => running = true,
+            _ => specs.push(arg.clone()),
+        }
+    }
+
+    let ids: Vec<usize> = if all || running {
+        with_shell(|shell| {
+            shell
+                .jobs
+                .jobs
+                .iter()
+                .filter(|job| !running || job.state() == JobState::Running)
+                .map(|job| job.id)
+                .collect()
+        })
+    } else if specs.is_empty() {
+        match with_shell(|shell| shell.jobs.resolve(None)) {
+            Ok(id) => vec![id],
+            Err(_) => {
+                cmd.stderr = "disown: current: no such job\n".to_string();
+                cmd.status = 1;
+                return;
+            }
+        }
+    } else {
+        let mut ids = Vec::new();
+        for spec in &specs {
+            match with_shell(|shell| shell.jobs.resolve(Some(spec))) {
+                Ok(id) => ids.push(id),
+                Err(e) => {
+                    cmd.stderr += &format!("disown: {}\n", e);
+                    cmd.status = 1;
+                }
+            }
+        }
+        ids
+    };
+
+    with_shell(|shell| {
+        for id in ids {
+            if keep {
+                // stays in the table but won't get SIGHUP
+                if let Some(job) = shell.jobs.get(id) {
+                    job.no_hup = true;
+                }
             } else {
-                cmd.stderr = format!("cd: {}: No such file or directory\n", dir);
+                shell.jobs.remove(id);
             }
         }
+    });
+}
+
+fn shopt(cmd: &mut Cmd) {
+    cmd.name = "shopt".to_string();
+    let mut set: Option<bool> = None;
+    let mut quiet = false;
+    let mut names: Vec<String> = Vec::new();
+    for arg in &cmd.args {
+        match arg.as_str() {
+            "-s" => set = Some(true),
+            "-u" => set = Some(false),
+            "-q" => quiet = true,
+            "-p" => {}
+            _ => names.push(arg.clone()),
+        }
+    }
+
+    for name in &names {
+        if !with_shell(|shell| shell.shopts.contains_key(name.as_str())) {
+            cmd.stderr += &format!("shopt: {}: invalid shell option name\n", name);
+            cmd.status = 1;
+        }
+    }
+    if cmd.status != 0 {
+        return;
+    }
+
+    let listing = names.is_empty();
+    with_shell(|shell| {
+        let names: Vec<&'static str> = if listing {
+            shell.shopts.keys().copied().collect()
+        } else {
+            shell
+                .shopts
+                .keys()
+                .copied()
+                .filter(|key| names.iter().any(|name| name == key))
+                .collect()
+        };
+
+        for name in names {
+            match set {
+                Some(value) if !listing => {
+                    shell.shopts.insert(name, value);
+                }
+                Some(value) => {
+                    // shopt -s / -u alone lists the options with that value
+                    if shell.shopts[name] == value {
+                        cmd.stdout +=
+                            &format!("{:<16}{}\n", name, if value { "on" } else { "off" });
+                    }
+                }
+                None => {
+                    let value = shell.shopts[name];
+                    if quiet {
+                        if !value {
+                            cmd.status = 1;
+                        }
+                    } else {
+                        cmd.stdout +=
+                            &format!("{:<16}{}\n", name, if value { "on" } else { "off" });
+                    }
+                }
+            }
+        }
+    });
+}
+
+// runs a file in the current shell, a name without a slash is looked up
+// in PATH before the current directory
+fn source(cmd: &mut Cmd) {
+    let name = cmd.name.clone();
+    let Some(file) = cmd.args.first().cloned() else {
+        cmd.stderr = format!("{}: filename argument required\n", name);
+        cmd.status = 2;
+        return;
+    };
+
+    let path = if file.contains('/') {
+        file.clone()
+    } else {
+        env::var_os("PATH")
+            .into_iter()
+            .flat_map(|paths| env::split_paths(&paths).collect::<Vec<_>>())
+            .map(|dir| dir.join(&file))
+            .find(|path| path.is_file())
+            .map(|path| path.display().to_string())
+            .unwrap_or(file.clone())
+    };
+
+    // arguments replace the positional parameters while the file runs
+    let args = cmd.args[1..].to_vec();
+    let saved = with_shell(|shell| {
+        shell.source_depth += 1;
+        (!args.is_empty()).then(|| std::mem::replace(&mut shell.positional, args))
+    });
+
+    let result = run_script(&path);
+    trap::run_trap("RETURN");
+
+    with_shell(|shell| {
+        shell.source_depth -= 1;
+        shell.returning = false;
+        if let Some(positional) = saved {
+            shell.positional = positional;
+        }
+    });
+    match result {
+        Ok(()) => cmd.status = with_shell(|shell| shell.last_status),
+        Err(e) => {
+            cmd.stderr = format!("{}: {}\n", name, e);
+            cmd.status = 1;
+        }
+    }
+}
+
+// exit [n] and logout [n], the status defaults to $?; logout only leaves
+// a login shell
+fn exit(cmd: &mut Cmd) {
+    let (last_status, interactive, login) =
+        with_shell(|shell| (shell.last_status, shell.interactive, shell.login));
+    if cmd.name == "logout" && !login {
+        cmd.stderr = "logout: not login shell: use `exit'\n".to_string();
+        cmd.status = 1;
+        return;
+    }
+    if cmd.args.len() > 1 {
+        cmd.stderr = format!("{}: too many arguments\n", cmd.name);
+        cmd.status = 1;
+        return;
+    }
+    if !confirm_exit() {
+        cmd.status = 1;
+        return;
+    }
+
+    if interactive {
+        eprintln!("{}", cmd.name);
+    }
+    // the shell still exits, as with a status of 2
+    let status = match cmd.args.first() {
+        None => last_status,
+        Some(arg) => match arg.parse::<i64>() {
+            Ok(n) => (n & 0xff) as i32,
+            Err(_) => {
+                eprintln!("{}: {}: numeric argument required", cmd.name, arg);
+                2
+            }
+        },
+    };
+    exit_shell(status)
+}
+
+fn return_builtin(cmd: &mut Cmd) {
+    cmd.name = "return".to_string();
+    if with_shell(|shell| shell.source_depth) == 0 {
+        cmd.stderr = "return: can only `return' from a sourced script\n".to_string();
+        cmd.status = 1;
+        return;
+    }
+
+    cmd.status = match cmd.args.first() {
+        None => with_shell(|shell| shell.last_status),
+        Some(arg) => match arg.parse::<i64>() {
+            Ok(n) => (n & 0xff) as i32,
+            Err(_) => {
+                cmd.stderr = format!("return: {}: numeric argument required\n", arg);
+                2
+            }
+        },
+    };
+    with_shell(|shell| shell.returning = true);
+}
+
+// trap [-lp] [[action] signal ...], an action of - resets the signals and
+// an empty one ignores them
+fn trap(cmd: &mut Cmd) {
+    cmd.name = "trap".to_string();
+    let all_args = cmd.args.clone();
+    let mut args = all_args.as_slice();
+    let mut print = false;
+    match args.first().map(String::as_str) {
+        Some("-l") => {
+            for (name, num) in SIGNALS {
+                cmd.stdout += &format!("{:2}) SIG{}\n", num, name);
+            }
+            return;
+        }
+        Some("-p") => {
+            print = true;
+            args = &args[1..];
+        }
+        Some("--") => args = &args[1..],
+        _ => {}
+    }
+
+    if print || args.is_empty() {
+        print_traps(cmd, args);
+        return;
+    }
+
+    // trap SIG... resets when the first operand is a signal number
+    let (action, specs) = if args[0] == "-" || args[0].parse::<u32>().is_ok() {
+        let specs = if args[0] == "-" { &args[1..] } else { args };
+        (None, specs)
+    } else {
+        (Some(args[0].clone()), &args[1..])
+    };
+
+    for spec in specs {
+        match trap_name(spec) {
+            Some(name) => set_trap(&name, action.clone()),
+            None => {
+                cmd.stderr += &format!("trap: {}: invalid signal specification\n", spec);
+                cmd.status = 1;
+            }
+        }
+    }
+}
+
+fn print_traps(cmd: &mut Cmd, specs: &[String]) {
+    let mut names = Vec::new();
+    for spec in specs {
+        match trap_name(spec) {
+            Some(name) => names.push(name),
+            None => {
+                cmd.stderr += &format!("trap: {}: invalid signal specification\n", spec);
+                cmd.status = 1;
+            }
+        }
+    }
+
+    with_shell(|shell| {
+        for (name, action) in &shell.traps {
+            if !specs.is_empty() && !names.contains(name) {
+                continue;
+            }
+            let signal = if PSEUDO_SIGNALS.contains(&name.as_str()) {
+                name.clone()
+            } else {
+                format!("SIG{}", name)
+            };
+            cmd.stdout += &format!("trap -- '{}' {}\n", action.replace('\'', "'\\''"), signal);
+        }
+    });
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+type Resource = libc::c_int;
+
+// (flag, resource, description, unit, bytes per unit)
+const LIMITS: &[(char, Resource, &str, &str, u64)] = &[
+    ('c', libc::RLIMIT_CORE, "core file size", "blocks", 1024),
+    ('d', libc::RLIMIT_DATA, "data seg size", "kbytes", 1024),
+    ('f', libc::RLIMIT_FSIZE, "file size", "blocks", 512),
+    (
+        'l',
+        libc::RLIMIT_MEMLOCK,
+        "max locked memory",
+        "kbytes",
+        1024,
+    ),
+    ('m', libc::RLIMIT_RSS, "max memory size", "kbytes", 1024),
+    ('n', libc::RLIMIT_NOFILE, "open files", "", 1),
+    ('s', libc::RLIMIT_STACK, "stack size", "kbytes", 1024),
+    ('t', libc::RLIMIT_CPU, "cpu time", "seconds", 1),
+    ('u', libc::RLIMIT_NPROC, "max user processes", "", 1),
+    ('v', libc::RLIMIT_AS, "virtual memory", "kbytes", 1024),
+];
+
+// ulimit [-SHa] [-cdflmnstuv] [limit], -f when no resource is given; a
+// new limit sets both the soft and hard one unless -S or -H says otherwise
+fn ulimit(cmd: &mut Cmd) {
+    cmd.name = "ulimit".to_string();
+    let (mut soft, mut hard, mut all) = (false, false, false);
+    let mut limits = Vec::new();
+    let mut value = None;
+
+    for arg in &cmd.args {
+        if let Some(flags) = arg.strip_prefix('-').filter(|flags| !flags.is_empty()) {
+            for flag in flags.chars() {
+                match flag {
+                    'S' => soft = true,
+                    'H' => hard = true,
+                    'a' => all = true,
+                    _ => match LIMITS.iter().find(|limit| limit.0 == flag) {
+                        Some(limit) => limits.push(limit),
+                        None => {
+                            cmd.stderr = format!("ulimit: -{}: invalid option\n", flag);
+                            cmd.status = 2;
+                            return;
+                        }
+                    },
+                }
+            }
+        } else {
+            value = Some(arg.clone());
+        }
+    }
+
+    if all {
+        limits = LIMITS.iter().collect();
+    } else if limits.is_empty() {
+        limits.push(&LIMITS[2]);
+    }
+
+    let Some(value) = value else {
+        for (flag, resource, desc, unit, factor) in &limits {
+            let Ok(limit) = get_rlimit(*resource) else {
+                continue;
+            };
+            let limit = if hard && !soft {
+                limit.rlim_max
+            } else {
+                limit.rlim_cur
+            };
+            let limit = match limit {
+                libc::RLIM_INFINITY => "unlimited".to_string(),
+                limit => (limit / factor).to_string(),
+            };
+            if limits.len() == 1 {
+                cmd.stdout += &format!("{}\n", limit);
+            } else if unit.is_empty() {
+                cmd.stdout += &format!("{:<32}{}\n", format!("{} (-{})", desc, flag), limit);
+            } else {
+                let label = format!("{} ({}, -{})", desc, unit, flag);
+                cmd.stdout += &format!("{:<32}{}\n", label, limit);
+            }
+        }
+        return;
+    };
+
+    for (_, resource, desc, _, factor) in &limits {
+        let new = match value.as_str() {
+            "unlimited" => libc::RLIM_INFINITY,
+            _ => match value.parse::<u64>() {
+                Ok(n) => n.saturating_mul(*factor),
+                Err(_) => {
+                    cmd.stderr = format!("ulimit: {}: invalid number\n", value);
+                    cmd.status = 1;
+                    return;
+                }
+            },
+        };
+        let result = get_rlimit(*resource).and_then(|mut limit| {
+            if soft || !hard {
+                limit.rlim_cur = new;
+            }
+            if hard || !soft {
+                limit.rlim_max = new;
+            }
+            match unsafe { libc::setrlimit(*resource, &limit) } {
+                0 => Ok(()),
+                _ => Err(std::io::Error::last_os_error()),
+            }
+        });
+        if let Err(err) = result {
+            cmd.stderr += &format!(
+                "ulimit: {}: cannot modify limit: {}\n",
+                desc,
+                strip_os_error(&err)
+            );
+            cmd.status = 1;
+        }
+    }
+}
+
+fn get_rlimit(resource: Resource) -> std::io::Result<libc::rlimit> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    match unsafe { libc::getrlimit(resource, &mut limit) } {
+        0 => Ok(limit),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+// umask [-pS] [mode], the mode is octal or symbolic like u=rwx,g=rx,o=
+fn umask(cmd: &mut Cmd) {
+    cmd.name = "umask".to_string();
+    let (mut symbolic, mut reusable) = (false, false);
+    let mut mode = None;
+    for arg in &cmd.args {
+        match arg.as_str() {
+            "-S" => symbolic = true,
+            "-p" => reusable = true,
+            _ => mode = Some(arg.clone()),
+        }
+    }
+
+    // reading the mask means setting it
+    let mask = unsafe {
+        let mask = libc::umask(0);
+        libc::umask(mask);
+        mask as u32
+    };
+
+    let Some(mode) = mode else {
+        let text = if symbolic {
+            let perms = !mask & 0o777;
+            ["u", "g", "o"]
+                .iter()
+                .enumerate()
+                .map(|(i, who)| {
+                    let bits = perms >> (6 - 3 * i) & 0o7;
+                    let rwx: String = [(4, 'r'), (2, 'w'), (1, 'x')]
+                        .iter()
+                        .filter(|(bit, _)| bits & bit != 0)
+                        .map(|(_, ch)| *ch)
+                        .collect();
+                    format!("{}={}", who, rwx)
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        } else {
+            format!("{:04o}", mask)
+        };
+        cmd.stdout = if reusable && symbolic {
+            format!("umask -S {}\n", text)
+        } else if reusable {
+            format!("umask {}\n", text)
+        } else {
+            format!("{}\n", text)
+        };
+        return;
+    };
+
+    let new = if mode.chars().all(|ch| ('0'..='7').contains(&ch)) {
+        u32::from_str_radix(&mode, 8)
+            .ok()
+            .filter(|mask| *mask <= 0o777)
+    } else {
+        symbolic_mask(&mode, mask)
+    };
+    match new {
+        Some(mask) => unsafe {
+            libc::umask(mask as libc::mode_t);
+        },
+        None => {
+            cmd.stderr = format!("umask: {}: invalid mode\n", mode);
+            cmd.status = 1;
+        }
+    }
+}
+
+// applies clauses like u+w,go-x,a=r to the permissions the mask allows
+fn symbolic_mask(mode: &str, mask: u32) -> Option<u32> {
+    let mut perms = !mask & 0o777;
+
+    for clause in mode.split(',') {
+        let op_pos = clause.find(['+', '-', '='])?;
+        let (who, rest) = clause.split_at(op_pos);
+        let mut who_bits = 0;
+        for ch in who.chars() {
+            who_bits |= match ch {
+                'u' => 0o700,
+                'g' => 0o070,
+                'o' => 0o007,
+                'a' => 0o777,
+                _ => return None,
+            };
+        }
+        if who_bits == 0 {
+            who_bits = 0o777;
+        }
+
+        let op = rest.chars().next()?;
+        let mut bits = 0;
+        for ch in rest[1..].chars() {
+            bits |= match ch {
+                'r' => 0o444,
+                'w' => 0o222,
+                'x' => 0o111,
+                _ => return None,
+            };
+        }
+        bits &= who_bits;
+
+        match op {
+            '+' => perms |= bits,
+            '-' => perms &= !bits,
+            _ => perms = perms & !who_bits | bits,
+        }
+    }
+
+    Some(!perms & 0o777)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::symbolic_mask;
+    use crate::{
+        handler::test_utils::{run, status},
+        state::with_shell,
+    };
+
+    #[test]
+    fn test_expressions() {
+        assert_eq!(status("test", &[]), 1);
+        assert_eq!(status("test", &["x"]), 0);
+        assert_eq!(status("test", &[""]), 1);
+        assert_eq!(status("test", &["-n", "x"]), 0);
+        assert_eq!(status("test", &["-z", "x"]), 1);
+        assert_eq!(status("test", &["a", "=", "a"]), 0);
+        assert_eq!(status("test", &["2", "-lt", "10"]), 0);
+        assert_eq!(status("test", &["!", "a", "!=", "a"]), 0);
+        assert_eq!(status("test", &["-d", "/", "-a", "-e", "/nonexistent"]), 1);
+        assert_eq!(status("test", &["-f", "/", "-o", "x", "=", "x"]), 0);
+        assert_eq!(
+            status("test", &["(", "a", "=", "b", ")", "-o", "1", "-eq", "1"]),
+            0
+        );
+    }
+
+    #[test]
+    fn bracket_needs_closing() {
+        assert_eq!(status("[", &["a", "=", "a", "]"]), 0);
+        let cmd = run("[", &["a", "=", "a"]);
+        assert_eq!(cmd.status, 2);
+        assert_eq!(cmd.stderr, "[: missing `]'\n");
+        assert_eq!(status("test", &["1", "-eq", "x"]), 2);
+        assert_eq!(status("test", &["a", "b", "c", "d"]), 2);
+    }
+
+    #[test]
+    fn set_options() {
+        let flags = || with_shell(|shell| shell.flags());
+        assert_eq!(status("set", &["-euf"]), 0);
+        assert!(flags().contains('e') && flags().contains('u') && flags().contains('f'));
+        assert_eq!(status("set", &["+e", "-o", "pipefail", "+o", "noglob"]), 0);
+        assert!(!flags().contains('e') && !flags().contains('f') && flags().contains('u'));
+        assert!(with_shell(|shell| shell.option("pipefail")));
+
+        let listing = run("set", &["-o"]).stdout;
+        assert!(listing.contains("pipefail       \ton\n"));
+        assert!(listing.contains("errexit        \toff\n"));
+        let restore = run("set", &["+o"]).stdout;
+        assert!(restore.contains("set -o nounset\n"));
+        assert!(restore.contains("set +o xtrace\n"));
+
+        let cmd = run("set", &["-o", "nosuch"]);
+        assert_eq!(cmd.status, 1);
+        let cmd = run("set", &["-Q"]);
+        assert_eq!(
+            (cmd.status, cmd.stderr.as_str()),
+            (2, "set: -Q: invalid option\n")
+        );
+    }
+
+    #[test]
+    fn set_dash_ends_options() {
+        let positional = || with_shell(|shell| shell.positional.clone());
+        status("set", &["-x", "--", "a", "b"]);
+        assert_eq!(positional(), ["a", "b"]);
+        status("set", &["-"]);
+        assert!(!with_shell(|shell| shell.option("xtrace")));
+        assert_eq!(positional(), ["a", "b"]);
+        status("set", &["-x"]);
+        status("set", &["-", "-e", "c"]);
+        assert!(!with_shell(
+            |shell| shell.option("xtrace") || shell.option("errexit")
+        ));
+        assert_eq!(positional(), ["-e", "c"]);
+        status("set", &["--"]);
+        assert!(positional().is_empty());
+    }
+
+    #[test]
+    fn symbolic_umask() {
+        assert_eq!(symbolic_mask("u=rwx,g=rx,o=", 0o022), Some(0o027));
+        assert_eq!(symbolic_mask("go-w", 0o002), Some(0o022));
+        assert_eq!(symbolic_mask("a+rw", 0o077), Some(0o011));
+        assert_eq!(symbolic_mask("=r", 0o022), Some(0o333));
+        assert_eq!(symbolic_mask("u+z", 0o022), None);
+        assert_eq!(symbolic_mask("urw", 0o022), None);
+    }
+
+    #[test]
+    fn echo_options() {
+        assert_eq!(run("echo", &["-n", "a", "b"]).raw_stdout, b"a b");
+        assert_eq!(run("echo", &[r"a\tb"]).raw_stdout, b"a\\tb\n");
+        assert_eq!(run("echo", &["-e", r"a\tb\cc"]).raw_stdout, b"a\tb");
+        assert_eq!(run("echo", &["-eE", r"a\n"]).raw_stdout, b"a\\n\n");
+        assert_eq!(run("echo", &["-nx", "--"]).raw_stdout, b"-nx --\n");
+    }
+
+    #[test]
+    fn xpg_echo_expands_by_default() {
+        with_shell(|shell| shell.shopts.insert("xpg_echo", true));
+        assert_eq!(run("echo", &[r"\x41"]).raw_stdout, b"A\n");
+        assert_eq!(run("echo", &["-E", r"\x41"]).raw_stdout, b"\\x41\n");
+    }
+
+    #[test]
+    fn echo_escapes_keep_bytes() {
+        let cmd = run("echo", &["-e", r"\xe2\x82\xac\0377"]);
+        assert_eq!(cmd.raw_stdout, b"\xe2\x82\xac\xff\n");
+    }
+
+    #[test]
+    fn type_keywords() {
+        assert_eq!(run("type", &["[["]).stdout, "[[ is a shell keyword\n");
+        assert_eq!(run("type", &["time"]).stdout, "time is a shell keyword\n");
+        assert_eq!(run("type", &["["]).stdout, "[ is a shell builtin\n");
     }
 }
@@ -1,20 +1,26 @@
 use crate::{
+    expand::expand_value,
     handler::{
         builtins::{handle_builtins, is_builtin},
-        handlers::{handle_pipe, handle_redir},
+        cond::handle_cond,
+        handlers::{handle_pipe, handle_redir, run_builtin},
     },
-    parser::parser::Parser,
+    parser::{assign::AssignValue, parser::Parser},
+    state::with_shell,
     tokenizer::Token,
-    utils::check_ext_cmd,
+    utils::quote_word,
 };
-use std::process::Command;
 
 #[derive(Debug)]
 pub struct Cmd {
     pub name: String,
     pub args: Vec<String>,
     pub stdout: String,
+    pub raw_stdout: Vec<u8>, // output that may not be UTF-8, after stdout
     pub stderr: String,
+    pub status: i32,
+    pub env: Vec<(String, String)>, // VAR=value cmd
+    pub pipestatus: Vec<i32>,       // status of each pipeline stage
 }
 
 impl Cmd {
@@ -23,14 +29,29 @@ impl Cmd {
             name: String::new(),
             args: Vec::new(),
             stdout: String::new(),
+            raw_stdout: Vec::new(),
             stderr: String::new(),
+            status: 0,
+            env: Vec::new(),
+            pipestatus: Vec::new(),
         }
     }
 
     pub fn handler(&mut self, cmd_toks: Vec<Token>, parser: Parser) {
         let cmd_strings: Vec<String> = cmd_toks.iter().map(|token| token.to_string()).collect();
+        trace(&cmd_strings, &parser);
+
+        if with_shell(|shell| shell.option("noclobber"))
+            && let Some(filename) = parser.redir.clobbered()
+        {
+            eprintln!("{}: cannot overwrite existing file", filename);
+            self.status = 1;
+            return;
+        }
 
         if cmd_strings.is_empty() {
+            self.assign(&parser);
+            handle_redir(self, parser);
             return;
         }
 
@@ -41,37 +62,85 @@ impl Cmd {
             Vec::new()
         };
 
-        if parser.pipe.commands.len() < 2 {
-            if is_builtin(self.name.clone()) {
-                handle_builtins(self);
-                handle_redir(self, parser);
-            } else {
-                let (found, _) = check_ext_cmd(&self.name);
-                if found {
-                    self.handle_external();
-                    handle_redir(self, parser);
-                } else {
-                    eprintln!("{}: command not found", self.name);
-                }
+        // assignments before a command only apply to its environment
+        for assignment in &parser.assign.assignments {
+            if let (None, AssignValue::Scalar(value)) = (&assignment.index, &assignment.value) {
+                self.env.push((assignment.name.clone(), value.clone()));
             }
+        }
+
+        if parser.pipe.commands.len() > 1 {
+            handle_pipe(self, parser);
+        } else if self.name == "declare" {
+            handle_builtins(self);
+            if self.status == 0 {
+                self.assign(&parser);
+            }
+            handle_redir(self, parser);
+        } else if self.name == "[[" {
+            handle_cond(self);
+            handle_redir(self, parser);
+        } else if is_builtin(self.name.clone()) {
+            run_builtin(self, &parser);
+            handle_redir(self, parser);
         } else {
             handle_pipe(self, parser);
         }
     }
 
-    fn handle_external(&mut self) {
-        let output = Command::new(self.name.clone())
-            .args(&self.args)
-            .output()
-            .expect("Failed to execute");
+    fn assign(&mut self, parser: &Parser) {
+        for assignment in &parser.assign.assignments {
+            if let Err(e) = with_shell(|shell| shell.assign(assignment)) {
+                self.stderr += &format!("{}\n", e);
+                self.status = 1;
+            }
+        }
+    }
+}
+
+// set -x prints each command after expansion, prefixed by PS4
+fn trace(cmd_strings: &[String], parser: &Parser) {
+    if !with_shell(|shell| shell.option("xtrace")) {
+        return;
+    }
+    let ps4 = expand_value(&with_shell(|shell| shell.get_var("PS4")).unwrap_or("+ ".to_string()));
 
-        self.stdout = format!("{}", String::from_utf8_lossy(&output.stdout).into_owned());
-        if !output.stderr.is_empty() {
-            self.stderr = format!("{}", String::from_utf8_lossy(&output.stderr).into_owned());
+    let mut lines: Vec<Vec<String>> = Vec::new();
+    let assignments: Vec<String> = parser
+        .assign
+        .assignments
+        .iter()
+        .filter_map(|assignment| match &assignment.value {
+            AssignValue::Scalar(value) => {
+                Some(format!("{}={}", assignment.name, quote_word(value)))
+            }
+            AssignValue::Array(_) => None,
+        })
+        .collect();
+    if !assignments.is_empty() {
+        lines.push(assignments);
+    }
+    if parser.pipe.commands.len() > 1 {
+        for toks in &parser.pipe.commands {
+            lines.push(
+                toks.iter()
+                    .map(|tok| quote_word(&tok.to_string()))
+                    .collect(),
+            );
         }
+    } else if !cmd_strings.is_empty() {
+        lines.push(cmd_strings.iter().map(|word| quote_word(word)).collect());
+    }
+
+    for line in lines {
+        eprintln!("{}{}", ps4, line.join(" "));
     }
 }
 
 pub fn get_builtins() -> Vec<&'static str> {
-    vec!["echo", "exit", "type", "pwd", "cd"]
+    vec![
+        "echo", "exit", "type", "pwd", "cd", "set", "shift", "declare", "test", "[", "jobs", "fg",
+        "bg", "wait", "kill", "disown", "shopt", "source", ".", "return", "trap", "ulimit",
+        "umask", "read", "printf", "logout",
+    ]
 }
@@ -0,0 +1,339 @@
+use crate::{
+    expand::{expand_glob, expand_regex, expand_value},
+    handler::cmds::Cmd,
+    state::with_shell,
+    utils::{pattern_match, regex_match},
+};
+use std::{
+    ffi::CString,
+    fs,
+    os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt},
+};
+
+// [[ expression ]], the operands are raw words, expanded without field
+// splitting or globbing
+pub fn handle_cond(cmd: &mut Cmd) {
+    if cmd.args.last().map(String::as_str) != Some("]]") {
+        cmd.stderr = "[[: syntax error: expected `]]'\n".to_string();
+        cmd.status = 2;
+        return;
+    }
+
+    let words = &cmd.args[..cmd.args.len() - 1];
+    let mut parser = CondParser {
+        words,
+        pos: 0,
+        skip: false,
+    };
+    let result = parser.parse_or().and_then(|res| match parser.peek() {
+        Some(word) => Err(format!("syntax error near `{}'", word)),
+        None => Ok(res),
+    });
+
+    match result {
+        Ok(true) => cmd.status = 0,
+        Ok(false) => cmd.status = 1,
+        Err(e) => {
+            cmd.stderr = format!("[[: {}\n", e);
+            cmd.status = 2;
+        }
+    }
+}
+
+struct CondParser<'a> {
+    words: &'a [String],
+    pos: usize,
+    // the right side of a short-circuited && or || is parsed but has no
+    // side effects
+    skip: bool,
+}
+
+impl CondParser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.words.get(self.pos).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Result<&str, String> {
+        let word = self
+            .words
+            .get(self.pos)
+            .ok_or("unexpected end of expression")?;
+        self.pos += 1;
+        Ok(word)
+    }
+
+    fn parse_or(&mut self) -> Result<bool, String> {
+        let mut res = self.parse_and()?;
+        while self.peek() == Some("||") {
+            self.pos += 1;
+            let skip = self.skip;
+            self.skip |= res;
+            let rhs = self.parse_and()?;
+            self.skip = skip;
+            res = res || rhs;
+        }
+        Ok(res)
+    }
+
+    fn parse_and(&mut self) -> Result<bool, String> {
+        let mut res = self.parse_not()?;
+        while self.peek() == Some("&&") {
+            self.pos += 1;
+            let skip = self.skip;
+            self.skip |= !res;
+            let rhs = self.parse_not()?;
+            self.skip = skip;
+            res = res && rhs;
+        }
+        Ok(res)
+    }
+
+    fn parse_not(&mut self) -> Result<bool, String> {
+        if self.peek() == Some("!") {
+            self.pos += 1;
+            return Ok(!self.parse_not()?);
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<bool, String> {
+        if self.peek() == Some("(") {
+            self.pos += 1;
+            let res = self.parse_or()?;
+            if self.next()? != ")" {
+                return Err("expected `)'".to_string());
+            }
+            return Ok(res);
+        }
+
+        let first = self.next()?.to_string();
+
+        // binary operators take precedence, so [[ -f == -f ]] compares strings
+        if let Some(op) = self.peek().filter(|op| is_binary_op(op)) {
+            let op = op.to_string();
+            self.pos += 1;
+            let rhs = self.next()?.to_string();
+            let lhs = expand_value(&first);
+            return match op.as_str() {
+                "==" | "=" => Ok(pattern_match(&expand_glob(&rhs), &lhs)),
+                "!=" => Ok(!pattern_match(&expand_glob(&rhs), &lhs)),
+                "=~" if self.skip => Ok(false),
+                "=~" => regex(&lhs, &expand_regex(&rhs)),
+                _ => binary_test(&op, &lhs, &expand_value(&rhs)),
+            };
+        }
+
+        if is_unary_op(&first)
+            && let Some(operand) = self.peek().filter(|w| !matches!(*w, "&&" | "||" | ")"))
+        {
+            let operand = expand_value(operand);
+            self.pos += 1;
+            return Ok(unary_test(&first, &operand));
+        }
+
+        Ok(!expand_value(&first).is_empty())
+    }
+}
+
+// =~ sets BASH_REMATCH to the match and its capture groups
+fn regex(s: &str, re: &str) -> Result<bool, String> {
+    let groups = regex_match(re, s)?;
+    let elements = groups
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|group| (None, group))
+        .collect();
+    with_shell(|shell| shell.set_array("BASH_REMATCH", elements))?;
+    Ok(groups.is_some())
+}
+
+fn is_binary_op(op: &str) -> bool {
+    matches!(
+        op,
+        "==" | "="
+            | "!="
+            | "=~"
+            | "<"
+            | ">"
+            | "-eq"
+            | "-ne"
+            | "-lt"
+            | "-le"
+            | "-gt"
+            | "-ge"
+            | "-nt"
+            | "-ot"
+            | "-ef"
+    )
+}
+
+pub fn is_unary_op(op: &str) -> bool {
+    matches!(
+        op,
+        "-a" | "-b"
+            | "-c"
+            | "-d"
+            | "-e"
+            | "-f"
+            | "-g"
+            | "-h"
+            | "-k"
+            | "-L"
+            | "-n"
+            | "-p"
+            | "-r"
+            | "-s"
+            | "-S"
+            | "-t"
+            | "-u"
+            | "-v"
+            | "-w"
+            | "-x"
+            | "-z"
+    )
+}
+
+pub fn unary_test(op: &str, operand: &str) -> bool {
+    match op {
+        "-n" => !operand.is_empty(),
+        "-z" => operand.is_empty(),
+        "-v" => with_shell(|shell| shell.get_var(operand).is_some()),
+        "-t" => operand
+            .parse::<i32>()
+            .is_ok_and(|fd| unsafe { libc::isatty(fd) } == 1),
+        "-r" => access(operand, libc::R_OK),
+        "-w" => access(operand, libc::W_OK),
+        "-x" => access(operand, libc::X_OK),
+        "-h" | "-L" => fs::symlink_metadata(operand).is_ok_and(|m| m.file_type().is_symlink()),
+        _ => {
+            let Ok(meta) = fs::metadata(operand) else {
+                return false;
+            };
+            let mode = meta.permissions().mode();
+            match op {
+                "-a" | "-e" => true,
+                "-f" => meta.is_file(),
+                "-d" => meta.is_dir(),
+                "-b" => meta.file_type().is_block_device(),
+                "-c" => meta.file_type().is_char_device(),
+                "-p" => meta.file_type().is_fifo(),
+                "-S" => meta.file_type().is_socket(),
+                "-s" => meta.len() > 0,
+                "-u" => mode & 0o4000 != 0,
+                "-g" => mode & 0o2000 != 0,
+                "-k" => mode & 0o1000 != 0,
+                _ => false,
+            }
+        }
+    }
+}
+
+pub fn binary_test(op: &str, lhs: &str, rhs: &str) -> Result<bool, String> {
+    match op {
+        "=" | "==" => Ok(lhs == rhs),
+        "!=" => Ok(lhs != rhs),
+        "<" => Ok(lhs < rhs),
+        ">" => Ok(lhs > rhs),
+        "-nt" | "-ot" => {
+            let mtime = |path: &str| fs::metadata(path).and_then(|m| m.modified()).ok();
+            Ok(match (mtime(lhs), mtime(rhs)) {
+                (Some(l), Some(r)) if op == "-nt" => l > r,
+                (Some(l), Some(r)) => l < r,
+                (Some(_), None) => op == "-nt",
+                (None, Some(_)) => op == "-ot",
+                (None, None) => false,
+            })
+        }
+        "-ef" => Ok(match (fs::metadata(lhs), fs::metadata(rhs)) {
+            (Ok(l), Ok(r)) => l.dev() == r.dev() && l.ino() == r.ino(),
+            _ => false,
+        }),
+        _ => {
+            let int = |s: &str| {
+                s.trim()
+                    .parse::<i64>()
+                    .map_err(|_| format!("{}: integer expression expected", s))
+            };
+            let (l, r) = (int(lhs)?, int(rhs)?);
+            match op {
+                "-eq" => Ok(l == r),
+                "-ne" => Ok(l != r),
+                "-lt" => Ok(l < r),
+                "-le" => Ok(l <= r),
+                "-gt" => Ok(l > r),
+                "-ge" => Ok(l >= r),
+                _ => Err(format!("{}: unknown operator", op)),
+            }
+        }
+    }
+}
+
+fn access(path: &str, mode: i32) -> bool {
+    match CString::new(path) {
+        Ok(path) => unsafe { libc::access(path.as_ptr(), mode) == 0 },
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        handler::test_utils::{run, status},
+        state::with_shell,
+    };
+
+    #[test]
+    fn patterns_and_strings() {
+        assert_eq!(status("[[", &["abc", "==", "a*", "]]"]), 0);
+        assert_eq!(status("[[", &["abc", "==", "'a*'", "]]"]), 1);
+        assert_eq!(status("[[", &["abc", "!=", "b?c", "]]"]), 0);
+        assert_eq!(status("[[", &["a", "<", "b", "]]"]), 0);
+        assert_eq!(status("[[", &["-n", "x", "]]"]), 0);
+        assert_eq!(status("[[", &["-z", "''", "]]"]), 0);
+        assert_eq!(status("[[", &["10", "-gt", "9", "]]"]), 0);
+    }
+
+    #[test]
+    fn logic_and_grouping() {
+        assert_eq!(
+            status("[[", &["a", "==", "b", "||", "c", "==", "c", "]]"]),
+            0
+        );
+        assert_eq!(
+            status(
+                "[[",
+                &["!", "(", "a", "==", "a", "&&", "b", "==", "c", ")", "]]"]
+            ),
+            0
+        );
+        assert_eq!(
+            status("[[", &["-e", "/", "&&", "!", "-e", "/nonexistent", "]]"]),
+            0
+        );
+    }
+
+    #[test]
+    fn regex_sets_bash_rematch() {
+        assert_eq!(
+            status("[[", &["key=value", "=~", "^([a-z]+)=(.*)$", "]]"]),
+            0
+        );
+        let rematch = with_shell(|shell| {
+            (0..3)
+                .map(|i| shell.vars["BASH_REMATCH"].get(&i.to_string()).unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(rematch, ["key=value", "key", "value"]);
+        assert_eq!(status("[[", &["é", "=~", "^.", "]]"]), 0);
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let cmd = run("[[", &["a", "==", "]]"]);
+        assert_eq!(cmd.status, 2);
+        assert_eq!(cmd.stderr, "[[: unexpected end of expression\n");
+        assert_eq!(status("[[", &["a", "b", "]]"]), 2);
+        assert_eq!(status("[[", &["x", "=~", "(", "]]"]), 2);
+    }
+}
@@ -4,16 +4,40 @@ use crate::{
         cmds::Cmd,
         utils::{append_err, append_out, print_err, print_out, write_err, write_out},
     },
+    jobs::{child_setup, exit_code, parent_setup, wait_foreground},
     parser::parser::Parser,
+    run_as_script,
+    state::with_shell,
+    utils::{check_ext_cmd, strip_os_error},
+};
+use libc::{
+    STDERR_FILENO, STDIN_FILENO, STDOUT_FILENO, c_char, close, dup, dup2, execv, fork, pipe,
 };
-use libc::{STDIN_FILENO, STDOUT_FILENO, close, dup2, fork, pipe, waitpid};
 use std::{
-    os::{raw::c_int, unix::process::CommandExt},
-    process::{Command, exit},
-    ptr::null_mut,
+    env,
+    ffi::CString,
+    fs,
+    io::{self, ErrorKind, Write, stdout},
+    iter,
+    os::{fd::IntoRawFd, raw::c_int, unix::fs::OpenOptionsExt},
+    process::exit,
+    ptr,
 };
 
 pub fn handle_pipe(cmd: &mut Cmd, parser: Parser) {
+    let cmd_text = parser
+        .pipe
+        .commands
+        .iter()
+        .map(|toks| {
+            toks.iter()
+                .map(|tok| tok.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join(" | ");
+
     unsafe {
         let mut pipes: Vec<[c_int; 2]> = Vec::new();
         for _ in 0..parser.pipe.commands.len() - 1 {
@@ -26,7 +50,10 @@ pub fn handle_pipe(cmd: &mut Cmd, parser: Parser) {
         }
 
         let mut children: Vec<i32> = Vec::new();
+        let mut pgid = 0;
+        let mut builtin_last = false;
         let last_cmd_index = parser.pipe.commands.len() - 1;
+        let _ = stdout().flush();
 
         for (i, cmd_toks) in parser.pipe.commands.iter().enumerate() {
             if cmd_toks.is_empty() {
@@ -51,15 +78,20 @@ pub fn handle_pipe(cmd: &mut Cmd, parser: Parser) {
 
             let is_last_cmd = i == last_cmd_index;
 
-            if is_last_cmd && is_builtin(command.clone()) {
+            // a builtin at the end of a pipeline runs in the shell itself,
+            // reading the pipe on its stdin
+            if is_last_cmd && last_cmd_index > 0 && is_builtin(command.clone()) {
                 cmd.name = command.clone();
                 cmd.args = args.clone();
-                handle_builtins(cmd);
-                handle_redir(cmd, parser);
-                return;
+                with_stdin(pipes[i - 1][0], || handle_builtins(cmd));
+                handle_redir(cmd, parser.clone());
+                builtin_last = true;
+                break;
             }
 
-            let pid = fork();
+            // a lone command in a background job's child becomes that child
+            let in_place = last_cmd_index == 0 && with_shell(|shell| shell.exec_in_place);
+            let pid = if in_place { 0 } else { fork() };
             if pid < 0 {
                 eprintln!("Fork failed");
                 for fds in &pipes {
@@ -71,6 +103,8 @@ pub fn handle_pipe(cmd: &mut Cmd, parser: Parser) {
 
             if pid == 0 {
                 // Child process
+                child_setup(pgid, true);
+
                 if i > 0 {
                     dup2(pipes[i - 1][0], STDIN_FILENO);
                 }
@@ -85,26 +119,69 @@ pub fn handle_pipe(cmd: &mut Cmd, parser: Parser) {
                     close(fds[1]);
                 }
 
-                if is_last_cmd {
+                if i == 0
+                    && let Err(e) = redirect_stdin(&parser)
+                {
+                    eprintln!("{}", e);
+                    exit(1);
+                }
+
+                if is_last_cmd && let Err(e) = redirect_fds(&parser) {
+                    eprintln!("{}", e);
+                    exit(1);
+                }
+
+                if is_builtin(command.clone()) {
                     let mut child_cmd = Cmd::new();
                     child_cmd.name = command.clone();
                     child_cmd.args = args.clone();
-                    handle_redir(&mut child_cmd, parser.clone());
+                    handle_builtins(&mut child_cmd);
+                    print!("{}", child_cmd.stdout);
+                    let _ = stdout().write_all(&child_cmd.raw_stdout);
+                    eprint!("{}", child_cmd.stderr);
+                    let _ = stdout().flush();
+                    exit(child_cmd.status);
                 }
 
-                // execute the command
-                if args.is_empty() {
-                    let error = Command::new(command).exec();
-                    eprintln!("Failed to execute command: {:?}", error);
-                } else {
-                    let mut exec_cmd = Command::new(command);
-                    exec_cmd.args(&args);
-                    let error = exec_cmd.exec();
-                    eprintln!("Failed to execute command: {:?}", error);
+                // execute the command, leading assignments belong to the
+                // first one
+                if i == 0 {
+                    for (name, value) in &cmd.env {
+                        // SAFETY: the forked child is single threaded
+                        env::set_var(name, value);
+                    }
+                }
+                let error = match command_path(&command) {
+                    Some(path) => {
+                        let error = exec(&path, &command, &args);
+                        // a file without a #! line is a script for us
+                        if error.raw_os_error() == Some(libc::ENOEXEC) {
+                            run_as_script(&path, args);
+                        }
+                        error
+                    }
+                    None => io::Error::from(ErrorKind::NotFound),
+                };
+                match error.kind() {
+                    ErrorKind::NotFound => {
+                        eprintln!("{}: command not found", command);
+                        exit(127);
+                    }
+                    ErrorKind::PermissionDenied => {
+                        eprintln!("{}: Permission denied", command);
+                        exit(126);
+                    }
+                    _ => {
+                        eprintln!("{}: {}", command, error);
+                        exit(126);
+                    }
                 }
-                exit(1);
             } else {
                 // Parent process
+                if pgid == 0 {
+                    pgid = pid;
+                }
+                parent_setup(pid, pgid);
                 children.push(pid);
 
                 // close pipe ends
@@ -123,11 +200,145 @@ pub fn handle_pipe(cmd: &mut Cmd, parser: Parser) {
             close(fds[1]);
         }
 
-        // wait for all child processes to complete
-        for child_pid in &children {
-            waitpid(*child_pid, null_mut(), 0);
+        if children.is_empty() {
+            return;
+        }
+
+        // wait for all child processes to complete, the pipeline status is
+        // the status of the last command, or with pipefail the last failing
+        // one
+        let mut codes: Vec<i32> = wait_foreground(pgid, &children, cmd_text)
+            .into_iter()
+            .map(exit_code)
+            .collect();
+        if builtin_last {
+            codes.push(cmd.status);
+        }
+        cmd.status = if with_shell(|shell| shell.option("pipefail")) {
+            codes
+                .iter()
+                .rev()
+                .find(|&&code| code != 0)
+                .copied()
+                .unwrap_or(0)
+        } else {
+            codes.last().copied().unwrap_or(0)
+        };
+        cmd.pipestatus = codes;
+    }
+}
+
+// finds the program a command runs, searching PATH unless it names a file
+fn command_path(command: &str) -> Option<String> {
+    if command.contains('/') {
+        Some(command.to_string())
+    } else {
+        check_ext_cmd(command).1.map(|p| p.display().to_string())
+    }
+}
+
+// replaces the process with the program, returning only on failure. execv
+// rather than execvp, which would hand ENOEXEC files to /bin/sh itself
+fn exec(path: &str, command: &str, args: &[String]) -> io::Error {
+    let argv = iter::once(command)
+        .chain(args.iter().map(String::as_str))
+        .map(CString::new)
+        .collect::<Result<Vec<_>, _>>();
+    let (Ok(path), Ok(argv)) = (CString::new(path), argv) else {
+        return io::Error::from(ErrorKind::InvalidInput);
+    };
+    let mut ptrs: Vec<*const c_char> = argv.iter().map(|arg| arg.as_ptr()).collect();
+    ptrs.push(ptr::null());
+    unsafe { execv(path.as_ptr(), ptrs.as_ptr()) };
+    io::Error::last_os_error()
+}
+
+// applies the pipeline's redirections to the file descriptors of a child
+fn redirect_fds(parser: &Parser) -> Result<(), String> {
+    if let Some((filename, is_append)) = &parser.redir.combined_file {
+        let fd = open_fd(filename, *is_append)?;
+        unsafe {
+            dup2(fd, STDOUT_FILENO);
+            dup2(fd, STDERR_FILENO);
+            close(fd);
         }
+        return Ok(());
     }
+
+    if let Some((filename, is_append)) = &parser.redir.stdout_file {
+        let fd = open_fd(filename, *is_append)?;
+        unsafe {
+            dup2(fd, STDOUT_FILENO);
+            close(fd);
+        }
+    }
+    if let Some((filename, is_append)) = &parser.redir.stderr_file {
+        let fd = open_fd(filename, *is_append)?;
+        unsafe {
+            dup2(fd, STDERR_FILENO);
+            close(fd);
+        }
+    }
+    Ok(())
+}
+
+// `< file` feeds the first command of the pipeline
+fn redirect_stdin(parser: &Parser) -> Result<(), String> {
+    if let Some(filename) = &parser.redir.stdin_file {
+        let fd = open_input(filename)?;
+        unsafe {
+            dup2(fd, STDIN_FILENO);
+            close(fd);
+        }
+    }
+    Ok(())
+}
+
+fn open_input(filename: &str) -> Result<c_int, String> {
+    fs::File::open(filename)
+        .map(|file| file.into_raw_fd())
+        .map_err(|e| format!("{}: {}", filename, strip_os_error(&e)))
+}
+
+/// Runs a builtin in the shell with its `< file` redirection, if any.
+pub fn run_builtin(cmd: &mut Cmd, parser: &Parser) {
+    let Some(filename) = &parser.redir.stdin_file else {
+        handle_builtins(cmd);
+        return;
+    };
+    match open_input(filename) {
+        Ok(fd) => {
+            with_stdin(fd, || handle_builtins(cmd));
+            unsafe { close(fd) };
+        }
+        Err(e) => {
+            cmd.stderr += &format!("{}\n", e);
+            cmd.status = 1;
+        }
+    }
+}
+
+// points the shell's stdin at fd while f runs
+fn with_stdin(fd: c_int, f: impl FnOnce()) {
+    unsafe {
+        let saved = dup(STDIN_FILENO);
+        dup2(fd, STDIN_FILENO);
+        f();
+        dup2(saved, STDIN_FILENO);
+        close(saved);
+    }
+}
+
+fn open_fd(filename: &str, is_append: bool) -> Result<c_int, String> {
+    fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(is_append)
+        .truncate(!is_append)
+        .mode(0o666)
+        .open(filename)
+        .map(|file| file.into_raw_fd())
+        .map_err(|e| format!("Could not write to {}: {}", filename, e))
 }
 
 pub fn handle_redir(cmd: &mut Cmd, parser: Parser) {
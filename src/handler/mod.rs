@@ -1,4 +1,9 @@
 mod builtins;
 pub mod cmds;
+mod cond;
 mod handlers;
+mod printf;
+mod read;
+#[cfg(test)]
+mod test_utils;
 mod utils;
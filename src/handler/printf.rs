@@ -0,0 +1,496 @@
+use crate::{handler::cmds::Cmd, parser::assign::is_name, state::with_shell, utils::quote_word};
+use libc::{c_char, c_double, c_int, c_longlong, c_ulonglong, size_t};
+use std::{ffi::CString, iter::Peekable, str::CharIndices};
+
+// a numeric argument as C's printf takes it
+#[derive(Clone, Copy)]
+enum Num {
+    Int(i64),
+    Uint(u64),
+    Float(f64),
+}
+
+// the arguments left for the conversions of the format
+struct Args<'a> {
+    args: &'a [String],
+    next: usize,
+}
+
+impl Args<'_> {
+    fn next(&mut self) -> Option<&str> {
+        let arg = self.args.get(self.next)?;
+        self.next += 1;
+        Some(arg)
+    }
+}
+
+// printf [-v var] format [arguments], the format is reused while
+// arguments remain; a missing argument is an empty string or zero
+pub fn printf(cmd: &mut Cmd) {
+    cmd.name = "printf".to_string();
+    let all_args = cmd.args.clone();
+    let mut args = all_args.as_slice();
+    let mut var = None;
+    match args.first().map(String::as_str) {
+        Some("-v") => {
+            let Some(name) = args.get(1) else {
+                cmd.stderr = "printf: -v: option requires an argument\n".to_string();
+                cmd.status = 2;
+                return;
+            };
+            if !is_name(name) {
+                cmd.stderr = format!("printf: `{}': not a valid identifier\n", name);
+                cmd.status = 2;
+                return;
+            }
+            var = Some(name.clone());
+            args = &args[2..];
+        }
+        Some("--") => args = &args[1..],
+        _ => {}
+    }
+    if args.first().map(String::as_str) == Some("--") {
+        args = &args[1..];
+    }
+
+    let Some((format, args)) = args.split_first() else {
+        cmd.stderr = "printf: usage: printf [-v var] format [arguments]\n".to_string();
+        cmd.status = 2;
+        return;
+    };
+
+    let mut out = Vec::new();
+    let mut args = Args { args, next: 0 };
+    loop {
+        let start = args.next;
+        match format_once(format, &mut args, &mut out, cmd) {
+            Ok(false) => {}
+            // \c in a %b argument ends the output
+            Ok(true) => break,
+            Err(e) => {
+                cmd.stderr += &format!("printf: {}\n", e);
+                cmd.status = 1;
+                break;
+            }
+        }
+        if args.next == start || args.next >= args.args.len() {
+            break;
+        }
+    }
+
+    match var {
+        Some(name) => {
+            let value = String::from_utf8_lossy(&out).into_owned();
+            with_shell(|shell| shell.set_var(&name, value));
+        }
+        None => cmd.raw_stdout = out,
+    }
+}
+
+// one pass over the format, true when \c stopped the output
+fn format_once(
+    format: &str,
+    args: &mut Args,
+    out: &mut Vec<u8>,
+    cmd: &mut Cmd,
+) -> Result<bool, String> {
+    let mut chars = format.char_indices().peekable();
+    while let Some((pos, ch)) = chars.next() {
+        if ch == '\\' {
+            let (bytes, used) = escape(&format[pos + 1..], false);
+            out.extend(bytes);
+            let end = pos + 1 + used;
+            while chars.next_if(|(i, _)| *i < end).is_some() {}
+            continue;
+        }
+        if ch != '%' {
+            out.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+            continue;
+        }
+        if next_if_eq(&mut chars, '%') {
+            out.push(b'%');
+            continue;
+        }
+
+        let mut flags = String::new();
+        while let Some((_, flag)) = chars.next_if(|(_, c)| "-+ #0".contains(*c)) {
+            flags.push(flag);
+        }
+        let mut width = None;
+        if next_if_eq(&mut chars, '*') {
+            let (n, _) = parse_int(args.next().unwrap_or(""));
+            if n < 0 {
+                flags.push('-');
+            }
+            width = Some(n.unsigned_abs() as usize);
+        } else {
+            let digits = take_digits(&mut chars);
+            if !digits.is_empty() {
+                width = digits.parse().ok();
+            }
+        }
+        let mut precision = None;
+        if next_if_eq(&mut chars, '.') {
+            precision = if next_if_eq(&mut chars, '*') {
+                let (n, _) = parse_int(args.next().unwrap_or(""));
+                (n >= 0).then_some(n as usize)
+            } else {
+                Some(take_digits(&mut chars).parse().unwrap_or(0))
+            };
+        }
+
+        let Some((_, conv)) = chars.next() else {
+            return Err(format!("`%{}': missing format character", flags));
+        };
+        let arg = args.next();
+        let text = match conv {
+            's' => arg.unwrap_or("").to_string(),
+            'q' => arg.map(quote_word).unwrap_or("''".to_string()),
+            'c' => arg
+                .and_then(|arg| arg.chars().next())
+                .map(String::from)
+                .unwrap_or_default(),
+            'b' => {
+                let (bytes, stop) = expand_escapes(arg.unwrap_or(""));
+                out.extend(pad(&bytes, &flags, width, precision));
+                if stop {
+                    return Ok(true);
+                }
+                continue;
+            }
+            'd' | 'i' | 'u' | 'x' | 'X' | 'o' | 'f' | 'F' | 'e' | 'E' | 'g' | 'G' => {
+                let arg = arg.unwrap_or("");
+                let num = match conv {
+                    'd' | 'i' => Num::Int(to_int(arg, cmd)),
+                    'u' | 'x' | 'X' | 'o' => Num::Uint(to_int(arg, cmd) as u64),
+                    _ => Num::Float(to_float(arg, cmd)),
+                };
+                out.extend(c_format(&flags, width, precision, conv, num));
+                continue;
+            }
+            _ => return Err(format!("`{}': invalid format character", conv)),
+        };
+        // precision truncates strings, not %c or %q
+        let precision = if conv == 's' { precision } else { None };
+        out.extend(pad(text.as_bytes(), &flags, width, precision));
+    }
+    Ok(false)
+}
+
+type FormatChars<'a> = Peekable<CharIndices<'a>>;
+
+fn next_if_eq(chars: &mut FormatChars, expected: char) -> bool {
+    chars.next_if(|(_, c)| *c == expected).is_some()
+}
+
+fn take_digits(chars: &mut FormatChars) -> String {
+    let mut digits = String::new();
+    while let Some((_, digit)) = chars.next_if(|(_, c)| c.is_ascii_digit()) {
+        digits.push(digit);
+    }
+    digits
+}
+
+// width and precision count bytes, as in C
+fn pad(text: &[u8], flags: &str, width: Option<usize>, precision: Option<usize>) -> Vec<u8> {
+    let text = &text[..precision.map_or(text.len(), |precision| precision.min(text.len()))];
+    let fill = vec![b' '; width.unwrap_or(0).saturating_sub(text.len())];
+    if flags.contains('-') {
+        [text, &fill].concat()
+    } else {
+        [&fill, text].concat()
+    }
+}
+
+// numbers go through the C library so the flags mean what they do in C
+fn c_format(
+    flags: &str,
+    width: Option<usize>,
+    precision: Option<usize>,
+    conv: char,
+    num: Num,
+) -> Vec<u8> {
+    let mut spec = format!("%{}", flags);
+    if let Some(width) = width {
+        spec += &width.to_string();
+    }
+    if let Some(precision) = precision {
+        spec += &format!(".{}", precision);
+    }
+    if !matches!(num, Num::Float(_)) {
+        spec += "ll";
+    }
+    spec.push(if conv == 'i' { 'd' } else { conv });
+    let Ok(spec) = CString::new(spec) else {
+        return Vec::new();
+    };
+
+    let len = snprintf(std::ptr::null_mut(), 0, &spec, num);
+    if len < 0 {
+        return Vec::new();
+    }
+    let mut buf = vec![0u8; len as usize + 1];
+    snprintf(buf.as_mut_ptr().cast(), buf.len(), &spec, num);
+    buf.truncate(len as usize);
+    buf
+}
+
+fn snprintf(buf: *mut c_char, size: size_t, spec: &CString, num: Num) -> c_int {
+    unsafe {
+        match num {
+            Num::Int(n) => libc::snprintf(buf, size, spec.as_ptr(), n as c_longlong),
+            Num::Uint(n) => libc::snprintf(buf, size, spec.as_ptr(), n as c_ulonglong),
+            Num::Float(x) => libc::snprintf(buf, size, spec.as_ptr(), x as c_double),
+        }
+    }
+}
+
+// a bad number is reported and printed as far as it could be read, one
+// out of range as the nearest that fits
+fn to_int(arg: &str, cmd: &mut Cmd) -> i64 {
+    let (n, error) = parse_int(arg);
+    if let Some(error) = error {
+        cmd.stderr += &format!("printf: {}: {}\n", arg, error);
+        cmd.status = 1;
+    }
+    n
+}
+
+fn to_float(arg: &str, cmd: &mut Cmd) -> f64 {
+    if let Some(code) = char_code(arg) {
+        return code as f64;
+    }
+    match arg.trim().parse::<f64>() {
+        Ok(x) => x,
+        Err(_) if arg.is_empty() => 0.0,
+        Err(_) => {
+            cmd.stderr += &format!("printf: {}: invalid number\n", arg);
+            cmd.status = 1;
+            0.0
+        }
+    }
+}
+
+// 'c or "c is the code of the character c
+fn char_code(arg: &str) -> Option<i64> {
+    let rest = arg.strip_prefix(['\'', '"'])?;
+    Some(rest.chars().next().map_or(0, |ch| ch as i64))
+}
+
+// decimal, 0x hex or 0 octal, and what was wrong with it if it wasn't all
+// a number that fits
+fn parse_int(arg: &str) -> (i64, Option<&'static str>) {
+    if let Some(code) = char_code(arg) {
+        return (code, None);
+    }
+    let trimmed = arg.trim_start();
+    let (negative, digits) = match trimmed.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    let (radix, digits) = if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X"))
+    {
+        (16, hex)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        (8, &digits[1..])
+    } else {
+        (10, digits)
+    };
+
+    let len = digits
+        .find(|c: char| !c.is_digit(radix))
+        .unwrap_or(digits.len());
+    let limit = if negative { i64::MIN } else { i64::MAX };
+    let n = match u64::from_str_radix(&digits[..len], radix) {
+        Ok(n) if negative => 0i64.checked_sub_unsigned(n),
+        Ok(n) => i64::try_from(n).ok(),
+        Err(_) if len == 0 => Some(0),
+        Err(_) => None,
+    };
+    match n {
+        None => (limit, Some("Result too large")),
+        Some(n) if arg.is_empty() || (len > 0 && len == digits.len()) => (n, None),
+        Some(n) => (n, Some("invalid number")),
+    }
+}
+
+/// Expands the escapes of echo -e and printf's %b, where octal is written
+/// \0nnn. The result is bytes since \xHH and octal escapes needn't make
+/// UTF-8. The flag is set when \c cut the text short.
+pub fn expand_escapes(text: &str) -> (Vec<u8>, bool) {
+    let mut out = Vec::new();
+    let mut rest = text;
+    while let Some(pos) = rest.find('\\') {
+        out.extend_from_slice(&rest.as_bytes()[..pos]);
+        rest = &rest[pos + 1..];
+        if rest.starts_with('c') {
+            return (out, true);
+        }
+        let (bytes, used) = escape(rest, true);
+        out.extend(bytes);
+        rest = &rest[used..];
+    }
+    out.extend_from_slice(rest.as_bytes());
+    (out, false)
+}
+
+// the bytes for the escape after a backslash and how many bytes of it
+// were used; octal is \nnn in formats and \0nnn for %b and echo
+fn escape(rest: &str, zero_octal: bool) -> (Vec<u8>, usize) {
+    let Some(ch) = rest.chars().next() else {
+        return (b"\\".to_vec(), 0);
+    };
+    let simple = match ch {
+        'a' => Some(b'\x07'),
+        'b' => Some(b'\x08'),
+        'e' | 'E' => Some(b'\x1b'),
+        'f' => Some(b'\x0c'),
+        'n' => Some(b'\n'),
+        'r' => Some(b'\r'),
+        't' => Some(b'\t'),
+        'v' => Some(b'\x0b'),
+        '\\' => Some(b'\\'),
+        '"' | '\'' if !zero_octal => Some(ch as u8),
+        _ => None,
+    };
+    if let Some(simple) = simple {
+        return (vec![simple], 1);
+    }
+
+    let (radix, skip, max) = match ch {
+        'x' => (16, 1, 2),
+        'u' => (16, 1, 4),
+        'U' => (16, 1, 8),
+        '0' if zero_octal => (8, 1, 3),
+        '0'..='7' if !zero_octal => (8, 0, 3),
+        _ => return (format!("\\{}", ch).into_bytes(), ch.len_utf8()),
+    };
+    let digits: String = rest[skip..]
+        .chars()
+        .take(max)
+        .take_while(|c| c.is_digit(radix))
+        .collect();
+    // \x with no digits stays as it is
+    if digits.is_empty() && radix == 16 {
+        return (format!("\\{}", ch).into_bytes(), 1);
+    }
+    let code = u32::from_str_radix(&digits, radix).unwrap_or(0);
+    // \u and \U are characters, \x and octal single bytes
+    let bytes = match ch {
+        'u' | 'U' => char::from_u32(code)
+            .map(|ch| ch.to_string().into_bytes())
+            .unwrap_or_default(),
+        _ => vec![code as u8],
+    };
+    (bytes, skip + digits.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::test_utils::run;
+
+    #[test]
+    fn parse_int_forms() {
+        assert_eq!(parse_int("42"), (42, None));
+        assert_eq!(parse_int("-7"), (-7, None));
+        assert_eq!(parse_int("0x1F"), (31, None));
+        assert_eq!(parse_int("010"), (8, None));
+        assert_eq!(parse_int("'A"), (65, None));
+        assert_eq!(parse_int(""), (0, None));
+        assert_eq!(parse_int("12abc"), (12, Some("invalid number")));
+        assert_eq!(parse_int("abc"), (0, Some("invalid number")));
+        assert_eq!(parse_int("-9223372036854775808"), (i64::MIN, None));
+    }
+
+    #[test]
+    fn out_of_range_numbers() {
+        let cmd = run(
+            "printf",
+            &["%d %d\n", "99999999999999999999", "-9223372036854775809"],
+        );
+        assert_eq!(
+            cmd.raw_stdout,
+            b"9223372036854775807 -9223372036854775808\n"
+        );
+        assert_eq!(
+            cmd.stderr,
+            "printf: 99999999999999999999: Result too large\n\
+             printf: -9223372036854775809: Result too large\n"
+        );
+        assert_eq!(cmd.status, 1);
+    }
+
+    #[test]
+    fn escape_forms() {
+        assert_eq!(escape("n", false), (b"\n".to_vec(), 1));
+        assert_eq!(escape("101x", false), (b"A".to_vec(), 3));
+        assert_eq!(escape("0101", true), (b"A".to_vec(), 4));
+        assert_eq!(escape("xe2", false), (vec![0xe2], 3));
+        assert_eq!(escape("u20ac", false), ("€".as_bytes().to_vec(), 5));
+        assert_eq!(escape("xg", false), (b"\\x".to_vec(), 1));
+        assert_eq!(escape("q", false), (b"\\q".to_vec(), 1));
+    }
+
+    #[test]
+    fn expand_escapes_stops_at_c() {
+        assert_eq!(expand_escapes(r"a\tb\cc"), (b"a\tb".to_vec(), true));
+        assert_eq!(expand_escapes(r"\0101\\"), (b"A\\".to_vec(), false));
+    }
+
+    #[test]
+    fn raw_bytes_are_kept() {
+        let cmd = run("printf", &[r"\xe2\x82\xac %b\n", r"\0342\0202\0254\xff"]);
+        assert_eq!(cmd.raw_stdout, b"\xe2\x82\xac \xe2\x82\xac\xff\n");
+    }
+
+    #[test]
+    fn conversions() {
+        let cmd = run(
+            "printf",
+            &[
+                "%5s|%-3s|%.2s|%d|%05.1f|%x|%c|%q",
+                "ab",
+                "c",
+                "xyz",
+                "-4",
+                "2.25",
+                "255",
+                "hi",
+                "a b",
+            ],
+        );
+        assert_eq!(cmd.raw_stdout, b"   ab|c  |xy|-4|002.2|ff|h|'a b'");
+        assert_eq!(
+            run("printf", &["%*d|%-*d|", "4", "7", "3", "1"]).raw_stdout,
+            b"   7|1  |"
+        );
+    }
+
+    #[test]
+    fn format_is_reused() {
+        assert_eq!(
+            run("printf", &["%s=%s;", "a", "1", "b"]).raw_stdout,
+            b"a=1;b=;"
+        );
+        assert_eq!(run("printf", &["x\n", "unused"]).raw_stdout, b"x\n");
+    }
+
+    #[test]
+    fn bad_number_is_reported() {
+        let cmd = run("printf", &["%d", "abc"]);
+        assert_eq!(cmd.raw_stdout, b"0");
+        assert_eq!(cmd.status, 1);
+        assert_eq!(cmd.stderr, "printf: abc: invalid number\n");
+    }
+
+    #[test]
+    fn assigns_with_v() {
+        let cmd = run("printf", &["-v", "out", "%s-%d", "x", "5"]);
+        assert!(cmd.raw_stdout.is_empty());
+        assert_eq!(
+            with_shell(|shell| shell.get_var("out")).as_deref(),
+            Some("x-5")
+        );
+    }
+}
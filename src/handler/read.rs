@@ -0,0 +1,418 @@
+use crate::{handler::cmds::Cmd, parser::assign::is_name, state::with_shell};
+use libc::{c_int, termios};
+use std::{
+    io::{Error, ErrorKind, Write, stderr},
+    time::{Duration, Instant},
+};
+
+// a read that timed out exits like one killed by SIGALRM
+const TIMEOUT_STATUS: i32 = 128 + libc::SIGALRM;
+
+struct ReadOpts {
+    raw: bool,              // -r, backslashes are plain characters
+    silent: bool,           // -s, no echo on a terminal
+    prompt: Option<String>, // -p
+    timeout: Option<f64>,   // -t, in seconds
+    nchars: Option<usize>,  // -n, stop after this many characters
+    delim: u8,              // -d, the line ends here instead of at a newline
+    array: Option<String>,  // -a, the fields become the elements
+    fd: c_int,              // -u
+}
+
+// how a read ended
+enum Ending {
+    Done,
+    Eof,
+    Timeout,
+}
+
+// read [-rs] [-p prompt] [-t timeout] [-n nchars] [-d delim] [-a array]
+// [-u fd] [name ...], the line is split on IFS into the names, the last
+// one taking the rest of it; REPLY gets the whole line when none is given
+pub fn read(cmd: &mut Cmd) {
+    cmd.name = "read".to_string();
+    let (opts, names) = match parse_opts(&cmd.args) {
+        Ok(parsed) => parsed,
+        Err((e, status)) => {
+            cmd.stderr = format!("read: {}\n", e);
+            cmd.status = status;
+            return;
+        }
+    };
+
+    if let Some(name) = names.iter().chain(&opts.array).find(|name| !is_name(name)) {
+        cmd.stderr = format!("read: `{}': not a valid identifier\n", name);
+        cmd.status = 1;
+        return;
+    }
+    if unsafe { libc::fcntl(opts.fd, libc::F_GETFD) } == -1 {
+        cmd.stderr = format!(
+            "read: {}: invalid file descriptor: Bad file descriptor\n",
+            opts.fd
+        );
+        cmd.status = 1;
+        return;
+    }
+
+    // -t 0 only tells whether input is waiting
+    if opts.timeout == Some(0.0) {
+        cmd.status = if poll_input(opts.fd, Duration::ZERO) {
+            0
+        } else {
+            1
+        };
+        return;
+    }
+
+    let is_tty = unsafe { libc::isatty(opts.fd) } == 1;
+    if is_tty && let Some(prompt) = &opts.prompt {
+        eprint!("{}", prompt);
+        let _ = stderr().flush();
+    }
+
+    let saved = if is_tty && (opts.silent || opts.nchars.is_some()) {
+        set_raw_mode(opts.fd, &opts)
+    } else {
+        None
+    };
+    let result = read_input(&opts);
+    if let Some(term) = saved {
+        unsafe { libc::tcsetattr(opts.fd, libc::TCSADRAIN, &term) };
+        // the newline typed by the user wasn't echoed
+        if opts.silent {
+            eprintln!();
+        }
+    }
+
+    let (input, ending) = match result {
+        Ok(read) => read,
+        Err(e) => {
+            cmd.stderr = format!("read: read error: {}: {}\n", opts.fd, e);
+            cmd.status = 1;
+            return;
+        }
+    };
+
+    // IFS=, read ... splits with the IFS given to the command
+    let ifs = cmd
+        .env
+        .iter()
+        .rfind(|(name, _)| name == "IFS")
+        .map(|(_, value)| value.clone())
+        .or_else(|| with_shell(|shell| shell.get_var("IFS")))
+        .unwrap_or(" \t\n".to_string());
+    let assigned = if let Some(array) = &opts.array {
+        let elements = split_fields(&input, ifs.as_bytes(), usize::MAX)
+            .into_iter()
+            .map(|field| (None, field))
+            .collect();
+        with_shell(|shell| shell.set_array(array, elements))
+    } else if names.is_empty() {
+        let line: Vec<u8> = input.iter().map(|(byte, _)| *byte).collect();
+        let line = String::from_utf8_lossy(&line).into_owned();
+        with_shell(|shell| shell.set_var("REPLY", line));
+        Ok(())
+    } else {
+        let mut fields = split_fields(&input, ifs.as_bytes(), names.len()).into_iter();
+        with_shell(|shell| {
+            for name in &names {
+                shell.set_var(name, fields.next().unwrap_or_default());
+            }
+        });
+        Ok(())
+    };
+    if let Err(e) = assigned {
+        cmd.stderr = format!("read: {}\n", e);
+        cmd.status = 1;
+        return;
+    }
+
+    cmd.status = match ending {
+        Ending::Done => 0,
+        Ending::Eof => 1,
+        Ending::Timeout => TIMEOUT_STATUS,
+    };
+}
+
+// flags can be combined and an option's value may be attached, as in -rn1
+fn parse_opts(args: &[String]) -> Result<(ReadOpts, Vec<String>), (String, i32)> {
+    let mut opts = ReadOpts {
+        raw: false,
+        silent: false,
+        prompt: None,
+        timeout: None,
+        nchars: None,
+        delim: b'\n',
+        array: None,
+        fd: libc::STDIN_FILENO,
+    };
+    let mut args = args.iter();
+    let mut names = Vec::new();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        let Some(flags) = arg.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+            names.push(arg.clone());
+            break;
+        };
+
+        for (pos, flag) in flags.char_indices() {
+            match flag {
+                'r' => opts.raw = true,
+                's' => opts.silent = true,
+                'p' | 't' | 'n' | 'd' | 'a' | 'u' => {
+                    let attached = &flags[pos + 1..];
+                    let value = if attached.is_empty() {
+                        args.next()
+                            .cloned()
+                            .ok_or((format!("-{}: option requires an argument", flag), 2))?
+                    } else {
+                        attached.to_string()
+                    };
+                    match flag {
+                        'p' => opts.prompt = Some(value),
+                        't' => {
+                            opts.timeout = Some(
+                                value
+                                    .parse::<f64>()
+                                    .ok()
+                                    .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                                    .ok_or((
+                                        format!("{}: invalid timeout specification", value),
+                                        1,
+                                    ))?,
+                            )
+                        }
+                        'n' => {
+                            opts.nchars = Some(
+                                value
+                                    .parse::<usize>()
+                                    .map_err(|_| (format!("{}: invalid number", value), 1))?,
+                            )
+                        }
+                        // -d '' ends the line at a NUL byte
+                        'd' => opts.delim = value.bytes().next().unwrap_or(0),
+                        'a' => opts.array = Some(value),
+                        _ => {
+                            opts.fd = value.parse::<c_int>().map_err(|_| {
+                                (
+                                    format!("{}: invalid file descriptor specification", value),
+                                    1,
+                                )
+                            })?
+                        }
+                    }
+                    break;
+                }
+                _ => return Err((format!("-{}: invalid option", flag), 2)),
+            }
+        }
+    }
+    names.extend(args.cloned());
+    Ok((opts, names))
+}
+
+// -s turns the echo off, -n makes each key available as it's typed
+fn set_raw_mode(fd: c_int, opts: &ReadOpts) -> Option<termios> {
+    let mut term: termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut term) } != 0 {
+        return None;
+    }
+    let saved = term;
+    if opts.silent {
+        term.c_lflag &= !libc::ECHO;
+    }
+    if opts.nchars.is_some() {
+        term.c_lflag &= !libc::ICANON;
+        term.c_cc[libc::VMIN] = 1;
+        term.c_cc[libc::VTIME] = 0;
+    }
+    unsafe { libc::tcsetattr(fd, libc::TCSADRAIN, &term) };
+    Some(saved)
+}
+
+// reads a byte at a time so nothing past the line is consumed; each byte
+// is paired with whether a backslash quoted it
+fn read_input(opts: &ReadOpts) -> Result<(Vec<(u8, bool)>, Ending), Error> {
+    let deadline = opts
+        .timeout
+        .map(|secs| Instant::now() + Duration::from_secs_f64(secs));
+    let mut input = Vec::new();
+    let mut chars = 0;
+    let mut escaped = false;
+    // continuation bytes still owed to the last multibyte character
+    let mut pending = 0;
+
+    loop {
+        if pending == 0 && opts.nchars.is_some_and(|n| chars >= n) {
+            return Ok((input, Ending::Done));
+        }
+        if let Some(deadline) = deadline
+            && !poll_input(opts.fd, deadline.saturating_duration_since(Instant::now()))
+        {
+            return Ok((input, Ending::Timeout));
+        }
+
+        let mut byte = 0u8;
+        let n = unsafe { libc::read(opts.fd, (&mut byte as *mut u8).cast(), 1) };
+        if n < 0 {
+            let err = Error::last_os_error();
+            if err.kind() == ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        if n == 0 {
+            return Ok((input, Ending::Eof));
+        }
+
+        if pending > 0 {
+            pending -= 1;
+            input.push((byte, escaped));
+            if pending == 0 {
+                escaped = false;
+            }
+            continue;
+        }
+        if escaped {
+            escaped = false;
+            // a backslash before the newline joins the next line
+            if byte != b'\n' {
+                input.push((byte, true));
+                chars += 1;
+                pending = utf8_len(byte) - 1;
+                escaped = pending > 0;
+            }
+            continue;
+        }
+        if byte == b'\\' && !opts.raw {
+            escaped = true;
+            continue;
+        }
+        if byte == opts.delim {
+            return Ok((input, Ending::Done));
+        }
+        input.push((byte, false));
+        chars += 1;
+        pending = utf8_len(byte) - 1;
+    }
+}
+
+// the length of the UTF-8 sequence a byte starts
+fn utf8_len(byte: u8) -> usize {
+    match byte {
+        0xf0..=0xf7 => 4,
+        0xe0..=0xef => 3,
+        0xc0..=0xdf => 2,
+        _ => 1,
+    }
+}
+
+fn poll_input(fd: c_int, timeout: Duration) -> bool {
+    let mut pfd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let millis = timeout.as_millis().min(c_int::MAX as u128) as c_int;
+    loop {
+        match unsafe { libc::poll(&mut pfd, 1, millis) } {
+            -1 if Error::last_os_error().kind() == ErrorKind::Interrupted => continue,
+            n => return n > 0,
+        }
+    }
+}
+
+// IFS whitespace around a field is dropped and a run of it separates
+// fields, any other IFS character separates them on its own; the last of
+// max fields takes the rest of the line. Quoted bytes never split.
+fn split_fields(input: &[(u8, bool)], ifs: &[u8], max: usize) -> Vec<String> {
+    let is_ifs = |(byte, quoted): &(u8, bool)| !quoted && ifs.contains(byte);
+    let is_ifs_ws = |item: &(u8, bool)| is_ifs(item) && matches!(item.0, b' ' | b'\t' | b'\n');
+    let text = |bytes: &[(u8, bool)]| {
+        let bytes: Vec<u8> = bytes.iter().map(|(byte, _)| *byte).collect();
+        String::from_utf8_lossy(&bytes).into_owned()
+    };
+
+    let mut fields = Vec::new();
+    let mut pos = input.iter().take_while(|item| is_ifs_ws(item)).count();
+    while pos < input.len() {
+        if fields.len() + 1 == max {
+            let mut rest = &input[pos..];
+            while rest.last().is_some_and(is_ifs_ws) {
+                rest = &rest[..rest.len() - 1];
+            }
+            fields.push(text(rest));
+            break;
+        }
+
+        let len = input[pos..].iter().take_while(|item| !is_ifs(item)).count();
+        fields.push(text(&input[pos..pos + len]));
+        pos += len;
+
+        // whitespace, at most one other IFS character, then whitespace
+        pos += input[pos..]
+            .iter()
+            .take_while(|item| is_ifs_ws(item))
+            .count();
+        if input
+            .get(pos)
+            .is_some_and(|item| is_ifs(item) && !is_ifs_ws(item))
+        {
+            pos += 1;
+            pos += input[pos..]
+                .iter()
+                .take_while(|item| is_ifs_ws(item))
+                .count();
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(line: &str, ifs: &str, max: usize) -> Vec<String> {
+        let input: Vec<(u8, bool)> = line.bytes().map(|byte| (byte, false)).collect();
+        split_fields(&input, ifs.as_bytes(), max)
+    }
+
+    #[test]
+    fn whitespace_runs_separate() {
+        assert_eq!(split("  a   b\tc  ", " \t\n", usize::MAX), ["a", "b", "c"]);
+        assert_eq!(split("", " \t\n", usize::MAX), Vec::<String>::new());
+    }
+
+    #[test]
+    fn last_field_takes_the_rest() {
+        assert_eq!(split("a b  c d  ", " \t\n", 2), ["a", "b  c d"]);
+        assert_eq!(split("  a b  ", " \t\n", 1), ["a b"]);
+    }
+
+    #[test]
+    fn other_ifs_characters_separate_alone() {
+        assert_eq!(split("a,b,,c", ",", usize::MAX), ["a", "b", "", "c"]);
+        assert_eq!(split("a , b", " ,", usize::MAX), ["a", "b"]);
+        assert_eq!(split("a:b:c", ":", 2), ["a", "b:c"]);
+    }
+
+    #[test]
+    fn empty_ifs_keeps_the_line() {
+        assert_eq!(split(" a b ", "", 2), [" a b "]);
+    }
+
+    #[test]
+    fn quoted_bytes_never_split() {
+        let input = [
+            (b'a', false),
+            (b' ', true),
+            (b'b', false),
+            (b' ', false),
+            (b'c', false),
+        ];
+        assert_eq!(split_fields(&input, b" ", usize::MAX), ["a b", "c"]);
+    }
+}
@@ -0,0 +1,17 @@
+use crate::handler::{builtins::handle_builtins, cmds::Cmd, cond::handle_cond};
+
+// runs a builtin on its own, leaving its output and status in the command
+pub fn run(name: &str, args: &[&str]) -> Cmd {
+    let mut cmd = Cmd::new();
+    cmd.name = name.to_string();
+    cmd.args = args.iter().map(|arg| arg.to_string()).collect();
+    match name {
+        "[[" => handle_cond(&mut cmd),
+        _ => handle_builtins(&mut cmd),
+    }
+    cmd
+}
+
+pub fn status(name: &str, args: &[&str]) -> i32 {
+    run(name, args).status
+}
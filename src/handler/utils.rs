@@ -2,29 +2,27 @@ use crate::{
     handler::cmds::Cmd,
     utils::{append_to_file, write_to_file},
 };
+use std::io::{Write, stdout};
 
 pub fn print_out(cmd: &mut Cmd) {
     if cmd.stdout.is_empty() {
-        print!("{}", "".to_string());
+        print!("");
     } else {
         print!("{}", cmd.stdout);
     }
+    if !cmd.raw_stdout.is_empty() {
+        let _ = stdout().write_all(&cmd.raw_stdout);
+    }
 }
 
 pub fn print_err(cmd: &mut Cmd) {
-    if cmd.stderr.is_empty() {
-        print!("{}", "".to_string());
-    } else {
-        print!("{}", cmd.stderr);
+    if !cmd.stderr.is_empty() {
+        eprint!("{}", cmd.stderr);
     }
 }
 
 pub fn write_out(cmd: &mut Cmd, filename: String) {
-    if cmd.stdout.is_empty() {
-        write_to_file("".as_bytes().to_vec(), filename);
-    } else {
-        write_to_file(cmd.stdout.as_bytes().to_vec(), filename);
-    }
+    write_to_file([cmd.stdout.as_bytes(), &cmd.raw_stdout].concat(), filename);
 }
 
 pub fn write_err(cmd: &mut Cmd, filename: String) {
@@ -36,11 +34,7 @@ pub fn write_err(cmd: &mut Cmd, filename: String) {
 }
 
 pub fn append_out(cmd: &mut Cmd, filename: String) {
-    if cmd.stdout.is_empty() {
-        append_to_file("".as_bytes().to_vec(), filename);
-    } else {
-        append_to_file(cmd.stdout.as_bytes().to_vec(), filename);
-    }
+    append_to_file([cmd.stdout.as_bytes(), &cmd.raw_stdout].concat(), filename);
 }
 
 pub fn append_err(cmd: &mut Cmd, filename: String) {
@@ -0,0 +1,626 @@
+use crate::{execute, signals, state::with_shell, tokenizer::Token};
+use libc::{
+    EINTR, SIG_IGN, SIGCONT, SIGINT, SIGPIPE, SIGTSTP, SIGTTIN, SIGTTOU, STDIN_FILENO, WCONTINUED,
+    WCOREDUMP, WEXITSTATUS, WIFCONTINUED, WIFEXITED, WIFSIGNALED, WIFSTOPPED, WNOHANG, WSTOPSIG,
+    WTERMSIG, WUNTRACED, dup2, fork, getpid, isatty, kill, setpgid, signal, tcsetpgrp, waitpid,
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Write, stdout},
+    os::{fd::AsRawFd, raw::c_int},
+    process::exit,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProcState {
+    Running,
+    Stopped,
+    Done(c_int), // raw wait status
+}
+
+#[derive(Debug, Clone)]
+pub struct Proc {
+    pub pid: i32,
+    pub state: ProcState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobState {
+    Running,
+    Stopped,
+    Done(i32), // exit status of the last process
+}
+
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: usize,
+    pub pgid: i32,
+    pub procs: Vec<Proc>,
+    pub cmd: String,
+    pub no_hup: bool, // disown -h
+}
+
+impl Job {
+    pub fn state(&self) -> JobState {
+        if self.procs.iter().any(|p| p.state == ProcState::Running) {
+            JobState::Running
+        } else if self.procs.iter().any(|p| p.state == ProcState::Stopped) {
+            JobState::Stopped
+        } else {
+            match self.procs.last().map(|p| p.state) {
+                Some(ProcState::Done(status)) => JobState::Done(exit_code(status)),
+                _ => JobState::Done(0),
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct JobTable {
+    pub jobs: Vec<Job>,
+    // job ids, most recently stopped or backgrounded last
+    order: Vec<usize>,
+    // exit codes of removed jobs' processes, for wait
+    pub finished: HashMap<i32, i32>,
+    // wait statuses of children reaped while they weren't in a job, like a
+    // pipeline's commands when a builtin at its end runs wait -n
+    pub reaped: HashMap<i32, c_int>,
+}
+
+impl JobTable {
+    pub fn new() -> Self {
+        JobTable {
+            jobs: Vec::new(),
+            order: Vec::new(),
+            finished: HashMap::new(),
+            reaped: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, pgid: i32, pids: &[i32], cmd: String) -> usize {
+        let id = self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        let procs = pids
+            .iter()
+            .map(|&pid| Proc {
+                pid,
+                state: ProcState::Running,
+            })
+            .collect();
+        self.jobs.push(Job {
+            id,
+            pgid,
+            procs,
+            cmd,
+            no_hup: false,
+        });
+        self.order.push(id);
+        id
+    }
+
+    pub fn remove(&mut self, id: usize) {
+        if let Some(job) = self.jobs.iter().find(|job| job.id == id) {
+            for proc in &job.procs {
+                if let ProcState::Done(status) = proc.state {
+                    self.finished.insert(proc.pid, exit_code(status));
+                }
+            }
+        }
+        self.jobs.retain(|job| job.id != id);
+        self.order.retain(|&i| i != id);
+    }
+
+    pub fn get(&mut self, id: usize) -> Option<&mut Job> {
+        self.jobs.iter_mut().find(|job| job.id == id)
+    }
+
+    // makes the job the current one
+    pub fn touch(&mut self, id: usize) {
+        self.order.retain(|&i| i != id);
+        self.order.push(id);
+    }
+
+    pub fn current(&self) -> Option<usize> {
+        self.order.last().copied()
+    }
+
+    pub fn previous(&self) -> Option<usize> {
+        self.order.iter().rev().nth(1).copied()
+    }
+
+    // '+' marks the current job, '-' the previous one
+    pub fn marker(&self, id: usize) -> char {
+        if Some(id) == self.current() {
+            '+'
+        } else if Some(id) == self.previous() {
+            '-'
+        } else {
+            ' '
+        }
+    }
+
+    pub fn update(&mut self, pid: i32, state: ProcState) {
+        let proc = self
+            .jobs
+            .iter_mut()
+            .find_map(|job| job.procs.iter_mut().find(|p| p.pid == pid));
+        match (proc, state) {
+            (Some(proc), state) => proc.state = state,
+            (None, ProcState::Done(status)) => {
+                self.reaped.insert(pid, status);
+            }
+            (None, _) => {}
+        }
+    }
+
+    /// Resolves a job spec: `%n`, `%+`, `%%`, `%-`, `%name` or `%?text`.
+    pub fn resolve(&self, spec: Option<&str>) -> Result<usize, String> {
+        let spec = spec.unwrap_or("%+");
+        let Some(body) = spec.strip_prefix('%') else {
+            // a bare pid
+            let pid = spec
+                .parse::<i32>()
+                .map_err(|_| format!("{}: no such job", spec))?;
+            return self
+                .jobs
+                .iter()
+                .find(|job| job.procs.iter().any(|p| p.pid == pid))
+                .map(|job| job.id)
+                .ok_or(format!("{}: no such job", spec));
+        };
+
+        let found = match body {
+            "" | "+" | "%" => self.current(),
+            "-" => self.previous(),
+            _ => {
+                if let Ok(n) = body.parse::<usize>() {
+                    self.jobs.iter().find(|job| job.id == n).map(|job| job.id)
+                } else {
+                    let matches: Vec<usize> = match body.strip_prefix('?') {
+                        Some(text) => self
+                            .jobs
+                            .iter()
+                            .filter(|job| job.cmd.contains(text))
+                            .map(|job| job.id)
+                            .collect(),
+                        None => self
+                            .jobs
+                            .iter()
+                            .filter(|job| job.cmd.starts_with(body))
+                            .map(|job| job.id)
+                            .collect(),
+                    };
+                    if matches.len() > 1 {
+                        return Err(format!("{}: ambiguous job spec", spec));
+                    }
+                    matches.first().copied()
+                }
+            }
+        };
+
+        found.ok_or(format!("{}: no such job", spec))
+    }
+}
+
+pub fn exit_code(status: c_int) -> i32 {
+    if WIFEXITED(status) {
+        WEXITSTATUS(status)
+    } else if WIFSIGNALED(status) {
+        128 + WTERMSIG(status)
+    } else if WIFSTOPPED(status) {
+        128 + WSTOPSIG(status)
+    } else {
+        1
+    }
+}
+
+pub fn format_state(state: JobState) -> String {
+    match state {
+        JobState::Running => "Running".to_string(),
+        JobState::Stopped => "Stopped".to_string(),
+        JobState::Done(0) => "Done".to_string(),
+        JobState::Done(code) => format!("Exit {}", code),
+    }
+}
+
+// puts an interactive shell in its own process group in the foreground
+pub fn init_job_control() {
+    unsafe {
+        if isatty(STDIN_FILENO) != 1 {
+            return;
+        }
+        signal(SIGTTIN, SIG_IGN);
+        signal(SIGTTOU, SIG_IGN);
+
+        let pid = getpid();
+        setpgid(0, 0);
+        tcsetpgrp(STDIN_FILENO, pid);
+    }
+    with_shell(|shell| shell.job_control = true);
+}
+
+/// Called in a forked child before running a command: joins the job's
+/// process group (a pgid of 0 starts a new one) and restores the signals
+/// the shell ignores.
+pub fn child_setup(pgid: i32, foreground: bool) {
+    let job_control = with_shell(|shell| shell.job_control);
+    unsafe {
+        if job_control {
+            setpgid(0, pgid);
+            if foreground {
+                let pgid = if pgid == 0 { getpid() } else { pgid };
+                tcsetpgrp(STDIN_FILENO, pgid);
+            }
+        }
+    }
+    signals::reset_child();
+}
+
+// same as child_setup, on the parent side to avoid racing the child
+pub fn parent_setup(pid: i32, pgid: i32) {
+    if with_shell(|shell| shell.job_control) {
+        unsafe { setpgid(pid, if pgid == 0 { pid } else { pgid }) };
+    }
+}
+
+/// Waits for a foreground job. If it gets stopped it is moved to the job
+/// table. Returns the raw wait status of each process.
+pub fn wait_foreground(pgid: i32, pids: &[i32], cmd: String) -> Vec<c_int> {
+    let job_control = with_shell(|shell| shell.job_control);
+    if job_control {
+        unsafe { tcsetpgrp(STDIN_FILENO, pgid) };
+    }
+
+    let mut statuses: Vec<Option<c_int>> = vec![None; pids.len()];
+    let mut stopped = false;
+    for (i, &pid) in pids.iter().enumerate() {
+        let mut status: c_int = 0;
+        while unsafe { waitpid(pid, &mut status, WUNTRACED) } < 0 {
+            if io::Error::last_os_error().raw_os_error() == Some(EINTR) {
+                continue;
+            }
+            // someone else already reaped it, a status we never saw is
+            // reported like a missing command rather than as success
+            status = with_shell(|shell| shell.jobs.reaped.remove(&pid)).unwrap_or(127 << 8);
+            break;
+        }
+        if WIFSTOPPED(status) {
+            stopped = true;
+            break;
+        }
+        statuses[i] = Some(status);
+    }
+
+    if job_control {
+        unsafe { tcsetpgrp(STDIN_FILENO, getpid()) };
+    }
+
+    if stopped {
+        let id = with_shell(|shell| {
+            let id = shell.jobs.add(pgid, pids, cmd.clone());
+            let job = shell.jobs.get(id).unwrap();
+            for (proc, status) in job.procs.iter_mut().zip(&statuses) {
+                proc.state = match status {
+                    Some(status) => ProcState::Done(*status),
+                    None => ProcState::Stopped,
+                };
+            }
+            id
+        });
+        eprintln!("\n[{}]+  {:<24}{}", id, "Stopped", cmd);
+        // report the job as stopped by SIGTSTP
+        return vec![0x7f | (SIGTSTP << 8)];
+    }
+
+    let statuses: Vec<c_int> = statuses.into_iter().flatten().collect();
+    report_signaled(&statuses);
+    statuses
+}
+
+// tells why a foreground job was killed, SIGINT and SIGPIPE deaths are
+// expected and stay quiet
+fn report_signaled(statuses: &[c_int]) {
+    let Some(&status) = statuses.iter().rev().find(|&&s| WIFSIGNALED(s)) else {
+        return;
+    };
+    match WTERMSIG(status) {
+        // move the prompt off the ^C line
+        SIGINT if with_shell(|shell| shell.job_control) => eprintln!(),
+        SIGINT | SIGPIPE => {}
+        sig if WCOREDUMP(status) => eprintln!("{} (core dumped)", signals::signal_description(sig)),
+        sig => eprintln!("{}", signals::signal_description(sig)),
+    }
+}
+
+/// Continues a job in the foreground and waits for it (fg).
+pub fn foreground(id: usize) -> i32 {
+    let Some(job) = with_shell(|shell| {
+        shell.jobs.touch(id);
+        shell.jobs.get(id).cloned()
+    }) else {
+        return 1;
+    };
+
+    println!("{}", job.cmd);
+    let _ = stdout().flush();
+    let job_control = with_shell(|shell| shell.job_control);
+    unsafe {
+        if job_control {
+            tcsetpgrp(STDIN_FILENO, job.pgid);
+        }
+        kill(-job.pgid, SIGCONT);
+    }
+
+    let mut stopped = false;
+    let mut last = 0;
+    let mut statuses = Vec::new();
+    for proc in &job.procs {
+        if let ProcState::Done(status) = proc.state {
+            last = status;
+            continue;
+        }
+        let mut status: c_int = 0;
+        if unsafe { waitpid(proc.pid, &mut status, WUNTRACED) } < 0 {
+            continue;
+        }
+        if WIFSTOPPED(status) {
+            with_shell(|shell| shell.jobs.update(proc.pid, ProcState::Stopped));
+            stopped = true;
+            break;
+        }
+        with_shell(|shell| shell.jobs.update(proc.pid, ProcState::Done(status)));
+        statuses.push(status);
+        last = status;
+    }
+
+    if job_control {
+        unsafe { tcsetpgrp(STDIN_FILENO, getpid()) };
+    }
+
+    if stopped {
+        with_shell(|shell| {
+            if let Some(job) = shell.jobs.get(id) {
+                for proc in &mut job.procs {
+                    if proc.state == ProcState::Running {
+                        proc.state = ProcState::Stopped;
+                    }
+                }
+            }
+        });
+        eprintln!("\n[{}]+  {:<24}{}", id, "Stopped", job.cmd);
+        return 128 + SIGTSTP;
+    }
+
+    report_signaled(&statuses);
+    with_shell(|shell| shell.jobs.remove(id));
+    exit_code(last)
+}
+
+/// Continues a stopped job in the background (bg).
+pub fn background(id: usize) -> Result<String, String> {
+    with_shell(|shell| {
+        let marker = shell.jobs.marker(id);
+        let job = shell.jobs.get(id).ok_or(format!("%{}: no such job", id))?;
+        if job.state() == JobState::Running {
+            return Err(format!("job {} already in background", id));
+        }
+        for proc in &mut job.procs {
+            if proc.state == ProcState::Stopped {
+                proc.state = ProcState::Running;
+            }
+        }
+        unsafe { kill(-job.pgid, SIGCONT) };
+        Ok(format!("[{}]{} {} &\n", id, marker, job.cmd))
+    })
+}
+
+// runs a pipeline in a forked child and records it as a job. The job
+// always gets its own process group so a signal for it reaches every
+// process, and a simple command is exec'd by the child itself so $! is
+// the command's pid.
+pub fn spawn(toks: Vec<Token>) {
+    let cmd = toks
+        .iter()
+        .map(|tok| tok.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let _ = stdout().flush();
+    let pid = unsafe { fork() };
+    if pid < 0 {
+        eprintln!("Fork failed");
+        return;
+    }
+
+    if pid == 0 {
+        unsafe { setpgid(0, 0) };
+        child_setup(0, false);
+        // without job control the job can't take the shell's input
+        if !with_shell(|shell| shell.job_control)
+            && let Ok(null) = File::open("/dev/null")
+        {
+            unsafe { dup2(null.as_raw_fd(), STDIN_FILENO) };
+        }
+        // time still has to report once the command is done
+        let timed = matches!(toks.first(), Some(Token::Word(word)) if word == "time");
+        with_shell(|shell| {
+            shell.interactive = false;
+            shell.job_control = false;
+            shell.exec_in_place = !timed;
+            shell.traps.retain(|_, action| action.is_empty());
+            // the shell's jobs aren't the child's to wait for or hang up
+            shell.jobs = JobTable::new();
+        });
+        let status = execute(toks);
+        let _ = stdout().flush();
+        exit(status);
+    }
+
+    unsafe { setpgid(pid, pid) };
+    let (id, interactive) = with_shell(|shell| {
+        shell.last_bg_pid = Some(pid);
+        (shell.jobs.add(pid, &[pid], cmd), shell.interactive)
+    });
+    if interactive {
+        eprintln!("[{}] {}", id, pid);
+    }
+}
+
+/// Blocks until every process of the job has finished (wait %n).
+pub fn wait_job(id: usize) -> i32 {
+    let Some(job) = with_shell(|shell| shell.jobs.get(id).cloned()) else {
+        return 127;
+    };
+
+    for proc in &job.procs {
+        if matches!(proc.state, ProcState::Done(_)) {
+            continue;
+        }
+        let mut status: c_int = 0;
+        if unsafe { waitpid(proc.pid, &mut status, WUNTRACED) } < 0 {
+            continue;
+        }
+        if WIFSTOPPED(status) {
+            with_shell(|shell| shell.jobs.update(proc.pid, ProcState::Stopped));
+            return exit_code(status);
+        }
+        with_shell(|shell| shell.jobs.update(proc.pid, ProcState::Done(status)));
+    }
+
+    with_shell(|shell| {
+        let code = match shell.jobs.get(id).map(|job| job.state()) {
+            Some(JobState::Done(code)) => code,
+            _ => 0,
+        };
+        shell.jobs.remove(id);
+        code
+    })
+}
+
+/// Blocks until the next job finishes (wait -n), None when there are no
+/// running jobs.
+pub fn wait_any() -> Option<i32> {
+    loop {
+        let done = with_shell(|shell| {
+            shell
+                .jobs
+                .jobs
+                .iter()
+                .find(|job| matches!(job.state(), JobState::Done(_)))
+                .map(|job| job.id)
+        });
+        if let Some(id) = done {
+            return Some(wait_job(id));
+        }
+
+        let running = with_shell(|shell| {
+            shell
+                .jobs
+                .jobs
+                .iter()
+                .any(|job| job.state() == JobState::Running)
+        });
+        if !running {
+            return None;
+        }
+
+        let mut status: c_int = 0;
+        let pid = unsafe { waitpid(-1, &mut status, WUNTRACED) };
+        if pid < 0 {
+            return None;
+        }
+        let state = if WIFSTOPPED(status) {
+            ProcState::Stopped
+        } else {
+            ProcState::Done(status)
+        };
+        with_shell(|shell| shell.jobs.update(pid, state));
+    }
+}
+
+/// Sends SIGHUP to the jobs, continuing stopped ones so they see it.
+/// Jobs marked with `disown -h` are left alone.
+pub fn hangup(stopped_only: bool) {
+    reap();
+    with_shell(|shell| {
+        for job in &shell.jobs.jobs {
+            let state = job.state();
+            if job.no_hup
+                || matches!(state, JobState::Done(_))
+                || (stopped_only && state != JobState::Stopped)
+            {
+                continue;
+            }
+            unsafe {
+                kill(-job.pgid, libc::SIGHUP);
+                if state == JobState::Stopped {
+                    kill(-job.pgid, SIGCONT);
+                }
+            }
+        }
+    });
+}
+
+pub fn has_stopped() -> bool {
+    reap();
+    with_shell(|shell| {
+        shell
+            .jobs
+            .jobs
+            .iter()
+            .any(|job| job.state() == JobState::Stopped)
+    })
+}
+
+// collects finished, stopped and continued children without blocking
+pub fn reap() {
+    loop {
+        let mut status: c_int = 0;
+        let pid = unsafe { waitpid(-1, &mut status, WNOHANG | WUNTRACED | WCONTINUED) };
+        if pid <= 0 {
+            break;
+        }
+
+        let state = if WIFSTOPPED(status) {
+            ProcState::Stopped
+        } else if WIFCONTINUED(status) {
+            ProcState::Running
+        } else {
+            ProcState::Done(status)
+        };
+        with_shell(|shell| shell.jobs.update(pid, state));
+    }
+}
+
+// reports and forgets finished jobs, called before each prompt
+pub fn notify() {
+    reap();
+    let interactive = with_shell(|shell| shell.interactive);
+
+    let done: Vec<(Job, char)> = with_shell(|shell| {
+        let done: Vec<(Job, char)> = shell
+            .jobs
+            .jobs
+            .iter()
+            .filter(|job| matches!(job.state(), JobState::Done(_)))
+            .map(|job| (job.clone(), shell.jobs.marker(job.id)))
+            .collect();
+        for (job, _) in &done {
+            shell.jobs.remove(job.id);
+        }
+        done
+    });
+
+    if !interactive {
+        return;
+    }
+    for (job, marker) in done {
+        eprintln!(
+            "[{}]{}  {:<24}{}",
+            job.id,
+            marker,
+            format_state(job.state()),
+            job.cmd
+        );
+    }
+}
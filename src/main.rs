@@ -1,46 +1,427 @@
 mod editor;
+mod expand;
 mod handler;
+mod jobs;
+mod options;
 mod parser;
+mod signals;
+mod state;
+mod timing;
 mod tokenizer;
+mod trap;
 mod utils;
 
-use crate::{handler::cmds::Cmd, parser::parser::Parser};
+use crate::{
+    handler::cmds::Cmd,
+    jobs::JobTable,
+    options::{Invocation, parse_args, usage},
+    parser::{
+        list::{List, Sep},
+        parser::Parser,
+    },
+    state::with_shell,
+    tokenizer::{Token, tokenize, unterminated},
+    utils::{read_line, strip_os_error},
+};
 use editor::EditHelper;
 use rustyline::{Config, Editor, error::ReadlineError};
-use std::process::exit;
+use std::{
+    env, fs,
+    io::{ErrorKind, Write},
+    path::Path,
+    process::exit,
+};
 
 pub fn evaluate(command: String) {
+    if command.trim().is_empty() {
+        return;
+    }
+
+    let mut list = List::new();
+    if let Err(e) = list.parse(tokenize(command.trim())) {
+        eprintln!("{}", e);
+        with_shell(|shell| shell.last_status = 2);
+        return;
+    }
+    // -n only checks the syntax of scripts
+    if with_shell(|shell| shell.option("noexec") && !shell.interactive) {
+        return;
+    }
+
+    // the separator before a pipeline decides whether it runs
+    let mut prev = Sep::Seq;
+    for (toks, sep) in list.items {
+        trap::run_pending();
+        let status = with_shell(|shell| shell.last_status);
+        let run = match prev {
+            Sep::And => status == 0,
+            Sep::Or => status != 0,
+            Sep::Seq | Sep::Background => true,
+        };
+        prev = sep;
+        if with_shell(|shell| shell.returning) {
+            break;
+        }
+        if !run {
+            continue;
+        }
+
+        trap::run_trap("DEBUG");
+        if sep == Sep::Background {
+            jobs::spawn(toks);
+            with_shell(|shell| shell.last_status = 0);
+        } else {
+            let status = execute(toks);
+            with_shell(|shell| shell.last_status = status);
+            // the ERR trap and set -e, unless the status is tested by && or ||
+            if status != 0 && sep == Sep::Seq {
+                trap::run_trap("ERR");
+                if with_shell(|shell| shell.option("errexit")) {
+                    exit_shell(status);
+                }
+            }
+        }
+    }
+    trap::run_pending();
+}
+
+// runs a single pipeline and returns its status
+pub fn execute(toks: Vec<Token>) -> i32 {
+    // time is a keyword covering the whole pipeline
+    if matches!(toks.first(), Some(Token::Word(word)) if word == "time") {
+        return timing::time_pipeline(&toks[1..]);
+    }
+
     let mut parser = Parser::new();
-    let cmd_toks = parser.parse(command);
+    let cmd_toks = parser.parse(toks);
+    if expand_failed() {
+        return 1;
+    }
     let mut cmd = Cmd::new();
     cmd.handler(cmd_toks, parser);
+    if expand_failed() {
+        return 1;
+    }
+
+    // a simple command is a pipeline of one
+    if cmd.pipestatus.is_empty() {
+        cmd.pipestatus.push(cmd.status);
+    }
+    let elements = cmd
+        .pipestatus
+        .iter()
+        .map(|code| (None, code.to_string()))
+        .collect();
+    let _ = with_shell(|shell| shell.set_array("PIPESTATUS", elements));
+    cmd.status
+}
+
+// an unset variable under set -u ends a non-interactive shell
+fn expand_failed() -> bool {
+    let (failed, interactive) =
+        with_shell(|shell| (std::mem::take(&mut shell.expand_failed), shell.interactive));
+    if failed && !interactive {
+        exit_shell(1);
+    }
+    failed
+}
+
+/// Runs a script a line at a time. A line ending inside quotes or with a
+/// backslash continues on the next one.
+pub fn run_lines(lines: impl Iterator<Item = String>) {
+    let mut pending = String::new();
+    for line in lines {
+        if with_shell(|shell| shell.returning) {
+            return;
+        }
+        pending.push_str(&line);
+        match unterminated(&pending) {
+            Some('\\') => {
+                pending.pop();
+            }
+            Some(_) => pending.push('\n'),
+            None => evaluate(std::mem::take(&mut pending)),
+        }
+    }
+
+    if unterminated(&pending).is_some() {
+        eprintln!(
+            "{}: unexpected EOF while looking for matching quote",
+            shell_name()
+        );
+        with_shell(|shell| shell.last_status = 2);
+    }
+}
+
+pub fn run_script(path: &str) -> Result<(), String> {
+    let text = fs::read(path).map_err(|e| format!("{}: {}", path, strip_os_error(&e)))?;
+    run_lines(String::from_utf8_lossy(&text).lines().map(String::from));
+    Ok(())
+}
+
+/// Runs an executable that has no `#!` line as a script in the forked
+/// child, the way POSIX shells handle ENOEXEC.
+pub fn run_as_script(path: &str, args: Vec<String>) -> ! {
+    with_shell(|shell| {
+        shell.name = path.to_string();
+        shell.positional = args;
+        shell.interactive = false;
+        shell.job_control = false;
+        shell.jobs = JobTable::new();
+        shell.traps.retain(|_, action| action.is_empty());
+        // the script is a shell of its own
+        shell.pid = unsafe { libc::getpid() };
+        shell.history.clear();
+    });
+    if let Err(e) = run_script(path) {
+        eprintln!("{}", e);
+        exit(126);
+    }
+    exit_shell(with_shell(|shell| shell.last_status));
+}
+
+fn shell_name() -> String {
+    with_shell(|shell| shell.name.clone())
+}
+
+/// Leaves the shell, hanging up its jobs: stopped ones always, all of
+/// them with `shopt -s huponexit`.
+pub fn exit_shell(status: i32) -> ! {
+    // a forked child leaves the shell's trap and jobs to the shell
+    if with_shell(|shell| shell.pid) != unsafe { libc::getpid() } {
+        exit(status);
+    }
+    // the EXIT trap runs once, even if it calls exit
+    if let Some(action) = with_shell(|shell| shell.traps.remove("EXIT")) {
+        with_shell(|shell| shell.last_status = status);
+        trap::run_action(&action);
+    }
+    let huponexit = with_shell(|shell| shell.shopts["huponexit"] && shell.interactive);
+    jobs::hangup(!huponexit);
+    save_history();
+    exit(status);
+}
+
+// HISTFILE, ~/.toysh_history when unset, an empty one keeps no history
+fn history_file() -> Option<String> {
+    match with_shell(|shell| shell.get_var("HISTFILE")) {
+        Some(path) => (!path.is_empty()).then_some(path),
+        None => env::var("HOME")
+            .ok()
+            .map(|home| format!("{}/.toysh_history", home)),
+    }
+}
+
+// appends this session's lines to the history file, forked children leave
+// that to the shell
+fn save_history() {
+    let lines = with_shell(|shell| {
+        if shell.pid != unsafe { libc::getpid() } {
+            return Vec::new();
+        }
+        std::mem::take(&mut shell.history)
+    });
+    if lines.is_empty() {
+        return;
+    }
+    let Some(path) = history_file() else {
+        return;
+    };
+    let text: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+    let result = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(text.as_bytes()));
+    if let Err(e) = result {
+        eprintln!("{}: {}: {}", shell_name(), path, strip_os_error(&e));
+    }
+}
+
+// exit and Ctrl-D warn once when jobs are stopped
+pub fn confirm_exit() -> bool {
+    let warned = with_shell(|shell| shell.exit_warned);
+    if warned || !jobs::has_stopped() {
+        return true;
+    }
+    eprintln!("There are stopped jobs.");
+    with_shell(|shell| shell.exit_warned = true);
+    false
+}
+
+// sources the file if it exists, startup files are optional
+fn run_startup_file(path: &str) {
+    if !Path::new(path).is_file() {
+        return;
+    }
+    with_shell(|shell| shell.source_depth += 1);
+    if let Err(e) = run_script(path) {
+        eprintln!("{}: {}", shell_name(), e);
+    }
+    with_shell(|shell| {
+        shell.source_depth -= 1;
+        shell.returning = false;
+    });
+}
+
+// login shells read the profiles, other interactive shells the rc file
+fn run_startup_files(inv: &Invocation, login: bool, interactive: bool) {
+    let home = env::var("HOME").unwrap_or_default();
+    if login {
+        if !inv.noprofile {
+            run_startup_file("/etc/profile");
+            run_startup_file(&format!("{}/.profile", home));
+        }
+    } else if interactive && !inv.norc {
+        let rcfile = inv
+            .rcfile
+            .clone()
+            .or_else(|| env::var("TOYSH_RC").ok())
+            .unwrap_or(format!("{}/.toyshrc", home));
+        run_startup_file(&rcfile);
+    }
 }
 
 fn main() -> rustyline::Result<()> {
+    let args: Vec<String> = env::args().collect();
+    let argv0 = args.first().cloned().unwrap_or(String::from("shell"));
+    with_shell(|shell| shell.name = argv0.clone());
+
+    let inv = match parse_args(args.get(1..).unwrap_or_default()) {
+        Ok(inv) => inv,
+        Err(e) => {
+            eprintln!("{}: {}", argv0, e);
+            exit(2);
+        }
+    };
+    if inv.version {
+        println!(
+            "{} version {}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        );
+        exit(0);
+    }
+    if inv.help {
+        print!("{}", usage(&argv0));
+        exit(0);
+    }
+    for (name, on) in &inv.options {
+        if let Err(e) = with_shell(|shell| shell.set_option(name, *on)) {
+            eprintln!("{}: {}", argv0, e);
+            exit(2);
+        }
+    }
+
+    // a login shell is started as -shell
+    let login = inv.login || argv0.starts_with('-');
+    with_shell(|shell| shell.login = login);
+    let mut operands = inv.operands.clone().into_iter();
+
+    // shell -c 'commands' [name [args...]]
+    if inv.command {
+        let command = operands.next().unwrap_or_default();
+        with_shell(|shell| {
+            if let Some(name) = operands.next() {
+                shell.name = name;
+            }
+            shell.positional = operands.collect();
+            shell.interactive = inv.interactive;
+        });
+        run_startup_files(&inv, login, inv.interactive);
+        run_lines(command.lines().map(String::from));
+        exit_shell(with_shell(|shell| shell.last_status));
+    }
+
+    // shell script.sh args...
+    if !inv.stdin
+        && let Some(path) = operands.next()
+    {
+        with_shell(|shell| {
+            shell.positional = operands.collect();
+            shell.interactive = false;
+        });
+        run_startup_files(&inv, login, false);
+        let text = match fs::read(&path) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("{}: {}: {}", argv0, path, strip_os_error(&e));
+                // a missing script is 127, one that can't be read 126
+                exit(if e.kind() == ErrorKind::NotFound {
+                    127
+                } else {
+                    126
+                });
+            }
+        };
+        with_shell(|shell| shell.name = path);
+        run_lines(String::from_utf8_lossy(&text).lines().map(String::from));
+        exit_shell(with_shell(|shell| shell.last_status));
+    }
+
+    // shell -s args...
+    with_shell(|shell| shell.positional = operands.collect());
+
+    // commands piped in, no prompt or line editing
+    if !inv.interactive && unsafe { libc::isatty(libc::STDIN_FILENO) } != 1 {
+        with_shell(|shell| shell.interactive = false);
+        run_startup_files(&inv, login, false);
+        run_lines(std::iter::from_fn(|| read_line(libc::STDIN_FILENO)));
+        exit_shell(with_shell(|shell| shell.last_status));
+    }
+
     let config = Config::builder()
         .completion_type(rustyline::CompletionType::List)
         .build();
 
+    signals::init_interactive();
+    jobs::init_job_control();
+    run_startup_files(&inv, login, true);
+
     let mut editor: Editor<EditHelper, _> = Editor::with_config(config)?;
     editor.set_helper(Some(EditHelper));
+    if let Some(path) = history_file() {
+        let _ = editor.load_history(&path);
+    }
 
     loop {
+        jobs::notify();
         let readline = editor.readline("$ ");
+        if signals::hup_received() {
+            // the terminal is gone
+            jobs::hangup(false);
+            exit_shell(129);
+        }
+
+        trap::run_pending();
+
+        // a warning only holds for the next command
+        let warned = with_shell(|shell| shell.exit_warned);
         match readline {
             Ok(line) => {
+                if !line.trim().is_empty() {
+                    let _ = editor.add_history_entry(line.as_str());
+                    with_shell(|shell| shell.history.push(line.clone()));
+                }
                 evaluate(line);
             }
             Err(ReadlineError::Interrupted) => {
-                exit(130);
+                // Ctrl-C discards the line
+                with_shell(|shell| shell.last_status = 130);
             }
             Err(ReadlineError::Eof) => {
-                break;
+                if confirm_exit() {
+                    break;
+                }
             }
             Err(err) => {
                 println!("Error: {:?}", err);
                 break;
             }
         }
+        if warned {
+            with_shell(|shell| shell.exit_warned = false);
+        }
     }
-    Ok(())
+    exit_shell(with_shell(|shell| shell.last_status));
 }
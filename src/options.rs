@@ -0,0 +1,162 @@
+use crate::state::option_name;
+
+/// How the shell was started, taken from its command line.
+#[derive(Debug, Default)]
+pub struct Invocation {
+    pub command: bool,     // -c, the first operand is the command string
+    pub stdin: bool,       // -s, commands come from stdin even with operands
+    pub interactive: bool, // -i
+    pub login: bool,
+    pub norc: bool,
+    pub noprofile: bool,
+    pub rcfile: Option<String>,
+    pub version: bool,
+    pub help: bool,
+    // set -o options turned on (true) or off (false) by -e, +x, -o name...
+    pub options: Vec<(String, bool)>,
+    // the script or command string, then $0 / positional parameters
+    pub operands: Vec<String>,
+}
+
+// options come before the first operand, `--` ends them
+pub fn parse_args(args: &[String]) -> Result<Invocation, String> {
+    let mut inv = Invocation::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-l" | "--login" => inv.login = true,
+            "--norc" => inv.norc = true,
+            "--noprofile" => inv.noprofile = true,
+            "--rcfile" | "--init-file" => {
+                let file = args
+                    .next()
+                    .ok_or(format!("{}: option requires an argument", arg))?;
+                inv.rcfile = Some(file.clone());
+            }
+            "--posix" => inv.options.push(("posix".to_string(), true)),
+            "--version" => inv.version = true,
+            "--help" => inv.help = true,
+            "--" | "-" => break,
+            _ if arg.starts_with("--") => return Err(format!("{}: invalid option", arg)),
+            _ if arg.len() > 1 && (arg.starts_with('-') || arg.starts_with('+')) => {
+                let on = arg.starts_with('-');
+                for flag in arg[1..].chars() {
+                    match flag {
+                        'c' if on => inv.command = true,
+                        's' if on => inv.stdin = true,
+                        'i' if on => inv.interactive = true,
+                        'l' if on => inv.login = true,
+                        'o' => {
+                            let name = args
+                                .next()
+                                .ok_or(format!("{}o: option requires an argument", &arg[..1]))?;
+                            inv.options.push((name.clone(), on));
+                        }
+                        _ => match option_name(flag) {
+                            Some(name) => inv.options.push((name.to_string(), on)),
+                            None => return Err(format!("{}{}: invalid option", &arg[..1], flag)),
+                        },
+                    }
+                }
+            }
+            _ => {
+                inv.operands.push(arg.clone());
+                break;
+            }
+        }
+    }
+    inv.operands.extend(args.cloned());
+
+    if inv.command && inv.operands.is_empty() {
+        return Err("-c: option requires an argument".to_string());
+    }
+    Ok(inv)
+}
+
+pub fn usage(name: &str) -> String {
+    format!(
+        "Usage: {name} [option ...] [script-file [arg ...]]
+       {name} [option ...] -c command [name [arg ...]]
+       {name} [option ...] -s [arg ...]
+
+Options:
+  -c              read commands from the first argument
+  -s              read commands from standard input
+  -i              run interactively
+  -l, --login     run as a login shell
+  -e -u -x -n -C -f
+                  the same flags as for set, +flag turns one off
+  -o option       turn on a set -o option, +o turns it off
+  --norc          don't read the rc file
+  --noprofile     don't read the login profiles
+  --rcfile FILE   read FILE instead of the rc file
+  --posix         turn on the posix option
+  --version       print the version and exit
+  --help          print this help and exit
+"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Invocation, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_args(&args)
+    }
+
+    #[test]
+    fn command_string_and_operands() {
+        let inv = parse(&["-c", "echo $0", "name", "arg"]).unwrap();
+        assert!(inv.command);
+        assert_eq!(inv.operands, ["echo $0", "name", "arg"]);
+        assert_eq!(
+            parse(&["-c"]).unwrap_err(),
+            "-c: option requires an argument"
+        );
+    }
+
+    #[test]
+    fn combined_flags_and_set_options() {
+        let inv = parse(&["-ex", "+u", "-o", "pipefail", "+o", "noglob"]).unwrap();
+        let on = |name: &str, value: bool| (name.to_string(), value);
+        assert_eq!(
+            inv.options,
+            [
+                on("errexit", true),
+                on("xtrace", true),
+                on("nounset", false),
+                on("pipefail", true),
+                on("noglob", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn long_options() {
+        let inv = parse(&["--login", "--norc", "--rcfile", "rc", "script"]).unwrap();
+        assert!(inv.login && inv.norc);
+        assert_eq!(inv.rcfile.as_deref(), Some("rc"));
+        assert_eq!(inv.operands, ["script"]);
+        assert_eq!(
+            parse(&["--posix"]).unwrap().options,
+            [("posix".to_string(), true)]
+        );
+        assert_eq!(
+            parse(&["--rcfile"]).unwrap_err(),
+            "--rcfile: option requires an argument"
+        );
+    }
+
+    #[test]
+    fn options_end_at_the_first_operand() {
+        let inv = parse(&["-s", "a", "-x"]).unwrap();
+        assert!(inv.stdin);
+        assert_eq!(inv.operands, ["a", "-x"]);
+        assert!(inv.options.is_empty());
+        assert_eq!(parse(&["--", "-e"]).unwrap().operands, ["-e"]);
+        assert_eq!(parse(&["-z"]).unwrap_err(), "-z: invalid option");
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{
+    expand::{expand_value, expand_word},
+    tokenizer::{Token, tokenize},
+};
+
+#[derive(Debug, Clone)]
+pub enum AssignValue {
+    Scalar(String),
+    Array(Vec<(Option<String>, String)>), // ([key]=)value
+}
+
+#[derive(Debug, Clone)]
+pub struct Assignment {
+    pub name: String,
+    pub index: Option<String>, // name[index]=value
+    pub append: bool,          // name+=value
+    pub value: AssignValue,
+}
+
+#[derive(Debug, Clone)]
+pub struct Assign {
+    pub assignments: Vec<Assignment>,
+}
+
+impl Assign {
+    pub fn new() -> Self {
+        Assign {
+            assignments: Vec::new(),
+        }
+    }
+
+    // strips the leading assignment words, everything after the first
+    // non-assignment word is left for the command
+    pub fn parse(&mut self, toks: Vec<Token>) -> Vec<Token> {
+        let mut toks = toks.into_iter().peekable();
+
+        while let Some(Token::Word(word)) = toks.peek() {
+            match parse_assignment(word) {
+                Some(assignment) => {
+                    self.assignments.push(assignment);
+                    toks.next();
+                }
+                None => break,
+            }
+        }
+
+        toks.collect()
+    }
+
+    // assignment words given as arguments (declare arr=(a b)) are taken out
+    // and replaced by the variable name
+    pub fn parse_args(&mut self, toks: Vec<Token>) -> Vec<Token> {
+        toks.into_iter()
+            .map(|tok| match &tok {
+                Token::Word(word) => match parse_assignment(word) {
+                    Some(assignment) => {
+                        let name = assignment.name.clone();
+                        self.assignments.push(assignment);
+                        Token::Word(name)
+                    }
+                    None => tok,
+                },
+                _ => tok,
+            })
+            .collect()
+    }
+}
+
+/// Parses a raw (unexpanded) word of the form `name=value`,
+/// `name[index]=value` or `name=(values...)`, expanding the value.
+pub fn parse_assignment(word: &str) -> Option<Assignment> {
+    let eq = word.find('=')?;
+    let (mut lhs, rhs) = (&word[..eq], &word[eq + 1..]);
+
+    let append = lhs.ends_with('+');
+    if append {
+        lhs = &lhs[..lhs.len() - 1];
+    }
+
+    let (name, index) = match lhs.find('[') {
+        Some(open) if lhs.ends_with(']') => (&lhs[..open], Some(&lhs[open + 1..lhs.len() - 1])),
+        Some(_) => return None,
+        None => (lhs, None),
+    };
+
+    if !is_name(name) {
+        return None;
+    }
+
+    let value = match rhs.strip_prefix('(').and_then(|r| r.strip_suffix(')')) {
+        Some(inner) if index.is_none() => AssignValue::Array(parse_elements(inner)),
+        _ => AssignValue::Scalar(expand_value(rhs)),
+    };
+
+    Some(Assignment {
+        name: name.to_string(),
+        index: index.map(expand_value),
+        append,
+        value,
+    })
+}
+
+fn parse_elements(inner: &str) -> Vec<(Option<String>, String)> {
+    let mut elements = Vec::new();
+
+    for tok in tokenize(inner) {
+        let Token::Word(word) = tok else {
+            continue;
+        };
+
+        // [key]=value
+        if word.starts_with('[')
+            && let Some(close) = word.find("]=")
+        {
+            let key = expand_value(&word[1..close]);
+            elements.push((Some(key), expand_value(&word[close + 2..])));
+            continue;
+        }
+
+        for field in expand_word(&word) {
+            elements.push((None, field));
+        }
+    }
+
+    elements
+}
+
+pub fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -0,0 +1,51 @@
+use crate::tokenizer::Token;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sep {
+    Seq,        // ;
+    And,        // &&
+    Or,         // ||
+    Background, // &
+}
+
+#[derive(Debug, Clone)]
+pub struct List {
+    pub items: Vec<(Vec<Token>, Sep)>, // (pipeline, separator after it)
+}
+
+impl List {
+    pub fn new() -> Self {
+        List { items: Vec::new() }
+    }
+
+    pub fn parse(&mut self, toks: Vec<Token>) -> Result<(), String> {
+        let mut cur: Vec<Token> = Vec::new();
+
+        for tok in toks {
+            let sep = match tok {
+                Token::Semicolon => Sep::Seq,
+                Token::DoubleAnd => Sep::And,
+                Token::DoublePipe => Sep::Or,
+                Token::And => Sep::Background,
+                _ => {
+                    cur.push(tok);
+                    continue;
+                }
+            };
+
+            if cur.is_empty() {
+                return Err(format!("syntax error near unexpected token `{}'", tok));
+            }
+            self.items.push((cur, sep));
+            cur = Vec::new();
+        }
+
+        if !cur.is_empty() {
+            self.items.push((cur, Sep::Seq));
+        } else if matches!(self.items.last(), Some((_, Sep::And | Sep::Or))) {
+            return Err("syntax error: unexpected end of file".to_string());
+        }
+
+        Ok(())
+    }
+}
@@ -1,3 +1,6 @@
+pub mod assign;
+pub mod list;
+#[allow(clippy::module_inception)]
 pub mod parser;
 mod pipe;
 mod redir;
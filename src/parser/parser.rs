@@ -1,10 +1,12 @@
 use crate::{
-    parser::{pipe::Pipe, redir::Redir},
-    tokenizer::{Token, tokenize},
+    expand::expand_tokens,
+    parser::{assign::Assign, pipe::Pipe, redir::Redir},
+    tokenizer::Token,
 };
 
 #[derive(Debug, Clone)]
 pub struct Parser {
+    pub assign: Assign,
     pub redir: Redir,
     pub pipe: Pipe,
 }
@@ -12,19 +14,26 @@ pub struct Parser {
 impl Parser {
     pub fn new() -> Self {
         Parser {
+            assign: Assign::new(),
             redir: Redir::new(),
             pipe: Pipe::new(),
         }
     }
 
-    pub fn parse(&mut self, cmd: String) -> Vec<Token> {
-        let toks: Vec<Token> = tokenize(cmd.trim());
+    pub fn parse(&mut self, toks: Vec<Token>) -> Vec<Token> {
+        let mut toks: Vec<Token> = self.assign.parse(toks);
+        if matches!(toks.first(), Some(Token::Word(word)) if word == "declare") {
+            toks = self.assign.parse_args(toks);
+        }
+
+        // operands of [[ are expanded when the expression is evaluated
+        let is_cond = matches!(toks.first(), Some(Token::Word(word)) if word == "[[");
+        let toks: Vec<Token> = if is_cond { toks } else { expand_tokens(toks) };
         if toks.is_empty() {
             return Vec::new();
         }
 
-        let mut cmd_toks = self.pipe.parse(toks.clone());
-        cmd_toks = self.redir.parse(cmd_toks);
-        return cmd_toks;
+        let cmd_toks = self.redir.parse(toks);
+        self.pipe.parse(cmd_toks)
     }
 }
@@ -32,6 +32,6 @@ impl Pipe {
             self.commands.push(cur_cmd);
         }
 
-        return cmd_toks;
+        cmd_toks
     }
 }
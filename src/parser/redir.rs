@@ -1,18 +1,24 @@
 use crate::tokenizer::Token;
+use std::path::Path;
 
 #[derive(Debug, Clone)]
 pub struct Redir {
+    pub stdin_file: Option<String>,
     pub stdout_file: Option<(String, bool)>, // (filename, is_append)
     pub stderr_file: Option<(String, bool)>,
     pub combined_file: Option<(String, bool)>,
+    // >| overwrites the file even with noclobber set
+    pub force: bool,
 }
 
 impl Redir {
     pub fn new() -> Self {
         Redir {
+            stdin_file: None,
             stdout_file: None,
             stderr_file: None,
             combined_file: None,
+            force: false,
         }
     }
 
@@ -31,6 +37,25 @@ impl Redir {
                         i += 1;
                     }
                 }
+                Token::RedirectIn => {
+                    if let Some(filename) = self.get_filename(&toks, i + 1) {
+                        self.stdin_file = Some(filename);
+                        i += 2;
+                    } else {
+                        cmd_toks.push(toks[i].clone());
+                        i += 1;
+                    }
+                }
+                Token::Clobber => {
+                    if let Some(filename) = self.get_filename(&toks, i + 1) {
+                        self.stdout_file = Some((filename, false));
+                        self.force = true;
+                        i += 2;
+                    } else {
+                        cmd_toks.push(toks[i].clone());
+                        i += 1;
+                    }
+                }
                 Token::AppendOut => {
                     if let Some(filename) = self.get_filename(&toks, i + 1) {
                         self.stdout_file = Some((filename, true));
@@ -86,6 +111,22 @@ impl Redir {
         cmd_toks
     }
 
+    /// The first file a `>` would truncate while noclobber is set. Only
+    /// existing regular files are protected, so `> /dev/null` still works.
+    pub fn clobbered(&self) -> Option<&str> {
+        let stdout = self.stdout_file.as_ref().filter(|_| !self.force);
+        [
+            stdout,
+            self.stderr_file.as_ref(),
+            self.combined_file.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|(_, is_append)| !is_append)
+        .map(|(filename, _)| filename.as_str())
+        .find(|filename| Path::new(filename).is_file())
+    }
+
     fn get_filename(&self, toks: &[Token], index: usize) -> Option<String> {
         if index < toks.len() {
             if let Token::Word(filename) = &toks[index] {
@@ -0,0 +1,191 @@
+use libc::{
+    SIG_DFL, SIG_IGN, SIGHUP, SIGINT, SIGPIPE, SIGQUIT, SIGTSTP, SIGTTIN, SIGTTOU, sigaction,
+    sigemptyset, signal,
+};
+use std::{
+    ffi::CStr,
+    mem,
+    os::raw::c_int,
+    ptr::null_mut,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
+
+static HUP_RECEIVED: AtomicBool = AtomicBool::new(false);
+static INTERACTIVE: AtomicBool = AtomicBool::new(false);
+// bit masks of signals with a trap action, ignored by trap '' and not yet
+// handled
+static CAUGHT: AtomicU64 = AtomicU64::new(0);
+static IGNORED: AtomicU64 = AtomicU64::new(0);
+static PENDING: AtomicU64 = AtomicU64::new(0);
+
+pub enum Disposition {
+    Catch,   // run the trap at the next safe point
+    Ignore,  // trap '' SIG
+    Default, // trap - SIG, what the shell does without a trap
+}
+
+extern "C" fn on_hup(_: c_int) {
+    HUP_RECEIVED.store(true, Ordering::SeqCst);
+    // the line editor retries interrupted reads, a closed stdin makes it
+    // return to the main loop
+    unsafe {
+        libc::close(libc::STDIN_FILENO);
+    }
+}
+
+extern "C" fn on_trap(sig: c_int) {
+    PENDING.fetch_or(1 << sig, Ordering::SeqCst);
+}
+
+fn install(sig: c_int, handler: extern "C" fn(c_int), flags: c_int) {
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = handler as *const () as usize;
+        action.sa_flags = flags;
+        sigemptyset(&mut action.sa_mask);
+        sigaction(sig, &action, null_mut());
+    }
+}
+
+// an interactive shell survives the keyboard signals meant for its jobs
+pub fn init_interactive() {
+    INTERACTIVE.store(true, Ordering::SeqCst);
+    unsafe {
+        signal(SIGINT, SIG_IGN);
+        signal(SIGQUIT, SIG_IGN);
+        signal(SIGTSTP, SIG_IGN);
+    }
+    // the jobs are hung up once the shell is back in the main loop
+    install(SIGHUP, on_hup, 0);
+}
+
+pub fn set_disposition(sig: c_int, disposition: Disposition) {
+    let bit = 1 << sig;
+    CAUGHT.fetch_and(!bit, Ordering::SeqCst);
+    IGNORED.fetch_and(!bit, Ordering::SeqCst);
+    match disposition {
+        Disposition::Catch => {
+            CAUGHT.fetch_or(bit, Ordering::SeqCst);
+            // waits for foreground jobs carry on, the trap runs after them
+            install(sig, on_trap, libc::SA_RESTART);
+        }
+        Disposition::Ignore => {
+            IGNORED.fetch_or(bit, Ordering::SeqCst);
+            unsafe { signal(sig, SIG_IGN) };
+        }
+        Disposition::Default => {
+            let interactive = INTERACTIVE.load(Ordering::SeqCst);
+            match sig {
+                SIGHUP if interactive => install(SIGHUP, on_hup, 0),
+                SIGINT | SIGQUIT | SIGTSTP if interactive => unsafe {
+                    signal(sig, SIG_IGN);
+                },
+                _ => unsafe {
+                    signal(sig, SIG_DFL);
+                },
+            }
+        }
+    }
+}
+
+/// Signals caught since the last call, lowest number first.
+pub fn take_pending() -> Vec<c_int> {
+    let pending = PENDING.swap(0, Ordering::SeqCst);
+    (1..64).filter(|sig| pending & (1 << sig) != 0).collect()
+}
+
+pub fn hup_received() -> bool {
+    HUP_RECEIVED.load(Ordering::SeqCst)
+}
+
+// restores the default dispositions in a forked child before exec
+pub fn reset_child() {
+    unsafe {
+        signal(SIGHUP, SIG_DFL);
+        signal(SIGINT, SIG_DFL);
+        signal(SIGQUIT, SIG_DFL);
+        signal(SIGTSTP, SIG_DFL);
+        signal(SIGTTIN, SIG_DFL);
+        signal(SIGTTOU, SIG_DFL);
+        // ignored by the rust runtime
+        signal(SIGPIPE, SIG_DFL);
+
+        // traps don't survive into children, ignored signals stay ignored
+        let (caught, ignored) = (
+            CAUGHT.load(Ordering::SeqCst),
+            IGNORED.load(Ordering::SeqCst),
+        );
+        for sig in 1..64 {
+            if caught & (1 << sig) != 0 {
+                signal(sig, SIG_DFL);
+            } else if ignored & (1 << sig) != 0 {
+                signal(sig, SIG_IGN);
+            }
+        }
+    }
+    INTERACTIVE.store(false, Ordering::SeqCst);
+    CAUGHT.store(0, Ordering::SeqCst);
+}
+
+pub const SIGNALS: &[(&str, c_int)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("URG", libc::SIGURG),
+    ("XCPU", libc::SIGXCPU),
+    ("XFSZ", libc::SIGXFSZ),
+    ("VTALRM", libc::SIGVTALRM),
+    ("PROF", libc::SIGPROF),
+    ("WINCH", libc::SIGWINCH),
+    ("IO", libc::SIGIO),
+    ("SYS", libc::SIGSYS),
+];
+
+// accepts TERM, SIGTERM, sigterm or 15
+pub fn signal_number(name: &str) -> Option<c_int> {
+    if let Ok(num) = name.parse::<c_int>() {
+        return (num == 0 || SIGNALS.iter().any(|(_, n)| *n == num)).then_some(num);
+    }
+    let upper = name.to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNALS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, num)| *num)
+}
+
+pub fn signal_name(num: c_int) -> Option<&'static str> {
+    SIGNALS
+        .iter()
+        .find(|(_, n)| *n == num)
+        .map(|(name, _)| *name)
+}
+
+// Segmentation fault, Killed, ...
+pub fn signal_description(num: c_int) -> String {
+    unsafe {
+        let desc = libc::strsignal(num);
+        if desc.is_null() {
+            return format!("Unknown signal {}", num);
+        }
+        CStr::from_ptr(desc).to_string_lossy().into_owned()
+    }
+}
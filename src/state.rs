@@ -0,0 +1,435 @@
+use crate::{
+    jobs::JobTable,
+    parser::assign::{AssignValue, Assignment},
+};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    env,
+};
+
+#[derive(Debug, Clone)]
+pub enum Var {
+    Scalar(String),
+    Indexed(BTreeMap<i64, String>),
+    Assoc(BTreeMap<String, String>),
+}
+
+impl Var {
+    pub fn values(&self) -> Vec<String> {
+        match self {
+            Var::Scalar(s) => vec![s.clone()],
+            Var::Indexed(arr) => arr.values().cloned().collect(),
+            Var::Assoc(map) => map.values().cloned().collect(),
+        }
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        match self {
+            Var::Scalar(_) => vec!["0".to_string()],
+            Var::Indexed(arr) => arr.keys().map(|k| k.to_string()).collect(),
+            Var::Assoc(map) => map.keys().cloned().collect(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        match self {
+            Var::Scalar(s) => (key == "0").then(|| s.clone()),
+            Var::Indexed(arr) => {
+                let mut idx = key.parse::<i64>().ok()?;
+                if idx < 0 {
+                    idx += arr.keys().next_back().map_or(0, |last| last + 1);
+                }
+                arr.get(&idx).cloned()
+            }
+            Var::Assoc(map) => map.get(key).cloned(),
+        }
+    }
+}
+
+// set -o options and their single letter flags
+pub const OPTIONS: &[(&str, Option<char>)] = &[
+    ("errexit", Some('e')),
+    ("noclobber", Some('C')),
+    ("noexec", Some('n')),
+    ("noglob", Some('f')),
+    ("nounset", Some('u')),
+    ("pipefail", None),
+    ("posix", None),
+    ("xtrace", Some('x')),
+];
+
+pub fn option_name(flag: char) -> Option<&'static str> {
+    OPTIONS
+        .iter()
+        .find(|(_, f)| *f == Some(flag))
+        .map(|(name, _)| *name)
+}
+
+#[derive(Debug)]
+pub struct Shell {
+    pub name: String,             // $0
+    pub positional: Vec<String>,  // $1, $2, ...
+    pub last_status: i32,         // $?
+    pub last_bg_pid: Option<i32>, // $!
+    pub pid: i32,                 // $$
+    pub interactive: bool,
+    pub login: bool,
+    pub job_control: bool,
+    // a background job's child runs its simple command without forking
+    pub exec_in_place: bool,
+    // shell variables, these are never exported
+    pub vars: HashMap<String, Var>,
+    pub jobs: JobTable,
+    // set -o options
+    pub options: BTreeMap<&'static str, bool>,
+    // shopt options
+    pub shopts: BTreeMap<&'static str, bool>,
+    // "There are stopped jobs." was printed, the next exit goes through
+    pub exit_warned: bool,
+    // files being run by source, return ends the innermost one
+    pub source_depth: usize,
+    pub returning: bool,
+    // an expansion failed (set -u), the command doesn't run
+    pub expand_failed: bool,
+    // trap actions by signal name or EXIT, ERR, DEBUG, RETURN
+    pub traps: BTreeMap<String, String>,
+    pub in_trap: bool,
+    // lines entered at the prompt, added to the history file on exit
+    pub history: Vec<String>,
+}
+
+impl Shell {
+    pub fn new() -> Self {
+        Shell {
+            name: String::from("shell"),
+            positional: Vec::new(),
+            last_status: 0,
+            last_bg_pid: None,
+            pid: unsafe { libc::getpid() },
+            interactive: true,
+            login: false,
+            job_control: false,
+            exec_in_place: false,
+            vars: HashMap::new(),
+            jobs: JobTable::new(),
+            options: OPTIONS.iter().map(|(name, _)| (*name, false)).collect(),
+            shopts: BTreeMap::from([("huponexit", false), ("xpg_echo", false)]),
+            exit_warned: false,
+            source_depth: 0,
+            returning: false,
+            expand_failed: false,
+            traps: BTreeMap::new(),
+            in_trap: false,
+            history: Vec::new(),
+        }
+    }
+
+    // scalar value of a variable, arrays give their first element
+    pub fn get_var(&self, name: &str) -> Option<String> {
+        match self.vars.get(name) {
+            Some(var) => var.get("0"),
+            None => env::var(name).ok(),
+        }
+    }
+
+    pub fn set_var(&mut self, name: &str, value: String) {
+        match self.vars.get_mut(name) {
+            Some(Var::Scalar(s)) => *s = value,
+            Some(Var::Indexed(arr)) => {
+                arr.insert(0, value);
+            }
+            Some(Var::Assoc(map)) => {
+                map.insert("0".to_string(), value);
+            }
+            None => {
+                if env::var_os(name).is_some() {
+                    // SAFETY: the shell is single threaded
+                    unsafe { env::set_var(name, value) };
+                } else {
+                    self.vars.insert(name.to_string(), Var::Scalar(value));
+                }
+            }
+        }
+    }
+
+    pub fn set_element(&mut self, name: &str, key: &str, value: String) -> Result<(), String> {
+        if !self.vars.contains_key(name) {
+            let arr = match env::var(name) {
+                Ok(val) => BTreeMap::from([(0, val)]),
+                Err(_) => BTreeMap::new(),
+            };
+            // SAFETY: the shell is single threaded
+            unsafe { env::remove_var(name) };
+            self.vars.insert(name.to_string(), Var::Indexed(arr));
+        }
+
+        let var = self.vars.get_mut(name).unwrap();
+        if let Var::Scalar(s) = var {
+            *var = Var::Indexed(BTreeMap::from([(0, std::mem::take(s))]));
+        }
+
+        match var {
+            Var::Indexed(arr) => {
+                let mut idx = key
+                    .trim()
+                    .parse::<i64>()
+                    .map_err(|_| format!("{}[{}]: bad array subscript", name, key))?;
+                if idx < 0 {
+                    idx += arr.keys().next_back().map_or(0, |last| last + 1);
+                    if idx < 0 {
+                        return Err(format!("{}[{}]: bad array subscript", name, key));
+                    }
+                }
+                arr.insert(idx, value);
+            }
+            Var::Assoc(map) => {
+                map.insert(key.to_string(), value);
+            }
+            Var::Scalar(_) => unreachable!(),
+        }
+        Ok(())
+    }
+
+    // name=(a b c) or name=([key]=a ...), replacing the previous value
+    pub fn set_array(
+        &mut self,
+        name: &str,
+        elements: Vec<(Option<String>, String)>,
+    ) -> Result<(), String> {
+        let is_assoc = matches!(self.vars.get(name), Some(Var::Assoc(_)));
+        let var = if is_assoc {
+            let mut map = BTreeMap::new();
+            for (key, value) in elements {
+                let key = key.ok_or(format!(
+                    "{}: must use subscript when assigning associative array",
+                    name
+                ))?;
+                map.insert(key, value);
+            }
+            Var::Assoc(map)
+        } else {
+            let mut arr = BTreeMap::new();
+            let mut next = 0;
+            for (key, value) in elements {
+                if let Some(key) = key {
+                    next = key
+                        .trim()
+                        .parse::<i64>()
+                        .map_err(|_| format!("{}[{}]: bad array subscript", name, key))?;
+                }
+                arr.insert(next, value);
+                next += 1;
+            }
+            Var::Indexed(arr)
+        };
+
+        // arrays live only in the shell variable store
+        // SAFETY: the shell is single threaded
+        unsafe { env::remove_var(name) };
+        self.vars.insert(name.to_string(), var);
+        Ok(())
+    }
+
+    pub fn assign(&mut self, assignment: &Assignment) -> Result<(), String> {
+        let name = assignment.name.as_str();
+        match (&assignment.index, &assignment.value) {
+            (Some(key), AssignValue::Scalar(value)) => {
+                let mut value = value.clone();
+                if assignment.append
+                    && let Some(old) = self.vars.get(name).and_then(|var| var.get(key))
+                {
+                    value = old + &value;
+                }
+                self.set_element(name, key, value)
+            }
+            (None, AssignValue::Scalar(value)) => {
+                let mut value = value.clone();
+                if assignment.append {
+                    value = self.get_var(name).unwrap_or_default() + &value;
+                }
+                self.set_var(name, value);
+                Ok(())
+            }
+            (_, AssignValue::Array(elements)) if assignment.append => {
+                for (key, value) in elements {
+                    let key = match (key, self.vars.get(name)) {
+                        (Some(key), _) => key.clone(),
+                        (None, Some(Var::Indexed(arr))) => arr
+                            .keys()
+                            .next_back()
+                            .map_or(0, |last| last + 1)
+                            .to_string(),
+                        (None, Some(Var::Scalar(_))) => "1".to_string(),
+                        (None, None) if env::var_os(name).is_some() => "1".to_string(),
+                        (None, _) => "0".to_string(),
+                    };
+                    self.set_element(name, &key, value.clone())?;
+                }
+                Ok(())
+            }
+            (_, AssignValue::Array(elements)) => self.set_array(name, elements.clone()),
+        }
+    }
+
+    // declare -a / declare -A
+    pub fn declare_array(&mut self, name: &str, assoc: bool) -> Result<(), String> {
+        match self.vars.get(name) {
+            Some(Var::Indexed(_)) if assoc => {
+                return Err(format!(
+                    "{}: cannot convert indexed to associative array",
+                    name
+                ));
+            }
+            Some(Var::Assoc(_)) if !assoc => {
+                return Err(format!(
+                    "{}: cannot convert associative to indexed array",
+                    name
+                ));
+            }
+            Some(Var::Indexed(_)) | Some(Var::Assoc(_)) => return Ok(()),
+            _ => {}
+        }
+
+        let value = self.get_var(name);
+        // SAFETY: the shell is single threaded
+        unsafe { env::remove_var(name) };
+        let var = if assoc {
+            Var::Assoc(value.map(|v| ("0".to_string(), v)).into_iter().collect())
+        } else {
+            Var::Indexed(value.map(|v| (0, v)).into_iter().collect())
+        };
+        self.vars.insert(name.to_string(), var);
+        Ok(())
+    }
+
+    pub fn option(&self, name: &str) -> bool {
+        self.options.get(name).copied().unwrap_or(false)
+    }
+
+    pub fn set_option(&mut self, name: &str, on: bool) -> Result<(), String> {
+        match self.options.get_mut(name) {
+            Some(value) => {
+                *value = on;
+                Ok(())
+            }
+            None => Err(format!("{}: invalid option name", name)),
+        }
+    }
+
+    // value of $-
+    pub fn flags(&self) -> String {
+        let mut flags: String = OPTIONS
+            .iter()
+            .filter(|(name, _)| self.option(name))
+            .filter_map(|(_, flag)| *flag)
+            .collect();
+        if self.interactive {
+            flags.push('i');
+        }
+        if self.job_control {
+            flags.push('m');
+        }
+        flags
+    }
+}
+
+thread_local! {
+    static SHELL: RefCell<Shell> = RefCell::new(Shell::new());
+}
+
+pub fn with_shell<R>(f: impl FnOnce(&mut Shell) -> R) -> R {
+    SHELL.with(|shell| f(&mut shell.borrow_mut()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        expand::{expand_value, expand_word},
+        parser::assign::parse_assignment,
+    };
+
+    fn assign(word: &str) -> Result<(), String> {
+        let assignment = parse_assignment(word).unwrap();
+        with_shell(|shell| shell.assign(&assignment))
+    }
+
+    fn keys(name: &str) -> Vec<String> {
+        with_shell(|shell| shell.vars[name].keys())
+    }
+
+    fn values(name: &str) -> Vec<String> {
+        with_shell(|shell| shell.vars[name].values())
+    }
+
+    #[test]
+    fn sparse_indices() {
+        assign("arr=([5]=a b [10]=c)").unwrap();
+        assert_eq!(keys("arr"), ["5", "6", "10"]);
+        assign("arr[2]=x").unwrap();
+        assign("arr[-1]=z").unwrap();
+        assert_eq!(keys("arr"), ["2", "5", "6", "10"]);
+        assert_eq!(values("arr"), ["x", "a", "b", "z"]);
+        assert_eq!(expand_value("${#arr[@]} ${!arr[@]}"), "4 2 5 6 10");
+        // negative indices count back from the last index, not the last element
+        assert_eq!(expand_value("${arr[-1]} ${arr[-2]}-"), "z -");
+        assert_eq!(
+            assign("arr[-20]=y").unwrap_err(),
+            "arr[-20]: bad array subscript"
+        );
+    }
+
+    #[test]
+    fn unset_elements_expand_to_nothing() {
+        assign("arr=([1]=a)").unwrap();
+        assert_eq!(expand_word("${arr[0]}"), Vec::<String>::new());
+        assert_eq!(expand_word("\"${arr[0]}\""), [""]);
+        with_shell(|shell| shell.options.insert("nounset", true));
+        expand_value("${arr[0]}");
+        assert!(with_shell(|shell| std::mem::take(&mut shell.expand_failed)));
+        expand_value("${arr[1]}");
+        assert!(!with_shell(|shell| shell.expand_failed));
+    }
+
+    #[test]
+    fn appending() {
+        assign("arr=(a)").unwrap();
+        assign("arr+=(b 'c d')").unwrap();
+        assert_eq!(values("arr"), ["a", "b", "c d"]);
+        assign("arr[1]+=x").unwrap();
+        assign("arr+=y").unwrap();
+        assert_eq!(values("arr"), ["ay", "bx", "c d"]);
+
+        assign("scalar=v").unwrap();
+        assign("scalar+=(w)").unwrap();
+        assert_eq!(values("scalar"), ["v", "w"]);
+    }
+
+    #[test]
+    fn associative_keys_with_spaces() {
+        with_shell(|shell| shell.declare_array("map", true)).unwrap();
+        assign("map=([\"a key\"]=1 [b]=2)").unwrap();
+        assign("map[\"other key\"]=3").unwrap();
+        assert_eq!(keys("map"), ["a key", "b", "other key"]);
+        assert_eq!(expand_word("\"${!map[@]}\""), ["a key", "b", "other key"]);
+        assert_eq!(expand_value("${map[a key]}"), "1");
+        assert_eq!(
+            assign("map=(x)").unwrap_err(),
+            "map: must use subscript when assigning associative array"
+        );
+    }
+
+    #[test]
+    fn declare_keeps_the_kind() {
+        with_shell(|shell| {
+            shell.set_var("kind", "v".to_string());
+            shell.declare_array("kind", false).unwrap();
+            assert_eq!(shell.vars["kind"].values(), ["v"]);
+            assert_eq!(
+                shell.declare_array("kind", true).unwrap_err(),
+                "kind: cannot convert indexed to associative array"
+            );
+        });
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{execute, state::with_shell, tokenizer::Token};
+use std::time::{Duration, Instant};
+
+const DEFAULT_FORMAT: &str = "\nreal\t%3lR\nuser\t%3lU\nsys\t%3lS";
+const POSIX_FORMAT: &str = "real %2R\nuser %2U\nsys %2S";
+
+/// `time [-p] pipeline`: runs the pipeline and reports the elapsed time
+/// and the CPU time of the shell and its children on stderr, formatted by
+/// TIMEFORMAT.
+pub fn time_pipeline(toks: &[Token]) -> i32 {
+    let posix = matches!(toks.first(), Some(Token::Word(word)) if word == "-p");
+    let toks = if posix { &toks[1..] } else { toks };
+
+    let start = Instant::now();
+    let (user_before, sys_before) = cpu_times();
+    let status = if toks.is_empty() {
+        0
+    } else {
+        execute(toks.to_vec())
+    };
+    let real = start.elapsed();
+    let (user_after, sys_after) = cpu_times();
+
+    let format = if posix {
+        POSIX_FORMAT.to_string()
+    } else {
+        with_shell(|shell| shell.get_var("TIMEFORMAT")).unwrap_or(DEFAULT_FORMAT.to_string())
+    };
+    // an empty TIMEFORMAT turns the report off
+    if !format.is_empty() {
+        let user = user_after.saturating_sub(user_before);
+        let sys = sys_after.saturating_sub(sys_before);
+        eprintln!("{}", format_times(&format, real, user, sys));
+    }
+    status
+}
+
+// user and system time of the shell plus its waited-for children
+fn cpu_times() -> (Duration, Duration) {
+    let mut user = Duration::ZERO;
+    let mut sys = Duration::ZERO;
+    for who in [libc::RUSAGE_SELF, libc::RUSAGE_CHILDREN] {
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        if unsafe { libc::getrusage(who, &mut usage) } == 0 {
+            user += timeval(usage.ru_utime);
+            sys += timeval(usage.ru_stime);
+        }
+    }
+    (user, sys)
+}
+
+fn timeval(tv: libc::timeval) -> Duration {
+    Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64)
+}
+
+// %[p][l]R, U and S are real, user and sys seconds with p decimals (3 at
+// most), l gives the long MmS.FFFs form; %P is the CPU percentage
+fn format_times(format: &str, real: Duration, user: Duration, sys: Duration) -> String {
+    let mut out = String::new();
+    let mut chars = format.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            out.push(ch);
+            continue;
+        }
+
+        let mut precision = 3;
+        if let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+            precision = digit.min(3) as usize;
+            chars.next();
+        }
+        let long = chars.next_if_eq(&'l').is_some();
+
+        let secs = match chars.next() {
+            Some('R') => real.as_secs_f64(),
+            Some('U') => user.as_secs_f64(),
+            Some('S') => sys.as_secs_f64(),
+            Some('P') => {
+                let cpu = (user + sys).as_secs_f64();
+                let real = real.as_secs_f64();
+                let percent = if real > 0.0 { cpu / real * 100.0 } else { 0.0 };
+                out += &format!("{:.2}", percent);
+                continue;
+            }
+            Some('%') => {
+                out.push('%');
+                continue;
+            }
+            Some(other) => {
+                out.push('%');
+                out.push(other);
+                continue;
+            }
+            None => {
+                out.push('%');
+                break;
+            }
+        };
+
+        if long {
+            let minutes = (secs / 60.0).floor();
+            out += &format!("{}m{:.*}s", minutes, precision, secs - minutes * 60.0);
+        } else {
+            out += &format!("{:.*}", precision, secs);
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(format: &str) -> String {
+        let ms = Duration::from_millis;
+        format_times(format, ms(61_500), ms(1_250), ms(250))
+    }
+
+    #[test]
+    fn default_and_posix_formats() {
+        assert_eq!(
+            format(DEFAULT_FORMAT),
+            "\nreal\t1m1.500s\nuser\t0m1.250s\nsys\t0m0.250s"
+        );
+        assert_eq!(format(POSIX_FORMAT), "real 61.50\nuser 1.25\nsys 0.25");
+    }
+
+    #[test]
+    fn precision_and_percent() {
+        assert_eq!(format("%0R %1U %9S"), "62 1.2 0.250");
+        assert_eq!(format("%P"), "2.44");
+        assert_eq!(format("100%% %x %"), "100% %x %");
+    }
+}
@@ -1,3 +1,6 @@
+use crate::parser::assign::is_name;
+use std::fmt;
+
 #[derive(Debug, Clone)]
 pub enum Token {
     Word(String),
@@ -5,8 +8,10 @@ pub enum Token {
     DoublePipe,   // ||
     And,          // &
     DoubleAnd,    // &&
+    RedirectIn,   // <
     RedirectOut,  // >
     AppendOut,    // >>
+    Clobber,      // >|
     RedirectErr,  // 2>
     AppendErr,    // 2>>
     RedirectBoth, // &>
@@ -16,23 +21,25 @@ pub enum Token {
     RightParen,   // )
 }
 
-impl Token {
-    pub fn to_string(&self) -> String {
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Token::Word(s) => s.clone(),
-            Token::Pipe => "|".to_string(),
-            Token::DoublePipe => "||".to_string(),
-            Token::And => "&".to_string(),
-            Token::DoubleAnd => "&&".to_string(),
-            Token::RedirectOut => ">".to_string(),
-            Token::AppendOut => ">>".to_string(),
-            Token::RedirectErr => "2>".to_string(),
-            Token::AppendErr => "2>>".to_string(),
-            Token::RedirectBoth => "&>".to_string(),
-            Token::AppendBoth => "&>>".to_string(),
-            Token::Semicolon => ";".to_string(),
-            Token::LeftParen => "(".to_string(),
-            Token::RightParen => ")".to_string(),
+            Token::Word(s) => write!(f, "{}", s),
+            Token::Pipe => write!(f, "|"),
+            Token::DoublePipe => write!(f, "||"),
+            Token::And => write!(f, "&"),
+            Token::DoubleAnd => write!(f, "&&"),
+            Token::RedirectIn => write!(f, "<"),
+            Token::RedirectOut => write!(f, ">"),
+            Token::AppendOut => write!(f, ">>"),
+            Token::Clobber => write!(f, ">|"),
+            Token::RedirectErr => write!(f, "2>"),
+            Token::AppendErr => write!(f, "2>>"),
+            Token::RedirectBoth => write!(f, "&>"),
+            Token::AppendBoth => write!(f, "&>>"),
+            Token::Semicolon => write!(f, ";"),
+            Token::LeftParen => write!(f, "("),
+            Token::RightParen => write!(f, ")"),
         }
     }
 }
@@ -42,11 +49,89 @@ pub fn tokenize(input: &str) -> Vec<Token> {
     convert_to_tokens(&string_tokens)
 }
 
-fn tokenize_to_strings(input: &str) -> Vec<String> {
-    let mut tokens = Vec::new();
+/// The quote left open at the end of the input, or `\` for a trailing line
+/// continuation. Either way the next line belongs to the same command.
+pub fn unterminated(input: &str) -> Option<char> {
+    let mut quote: Option<char> = None;
+    let mut word_start = true;
+    let mut chars = input.chars();
+
+    while let Some(ch) = chars.next() {
+        match (quote, ch) {
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => {
+                let Some(_) = chars.next() else {
+                    return Some('\\');
+                };
+            }
+            (Some('"'), '"') => quote = None,
+            (None, '\'' | '"') => quote = Some(ch),
+            (None, '#') if word_start => {
+                // comments can't open quotes
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                word_start = true;
+                continue;
+            }
+            _ => {}
+        }
+        word_start = quote.is_none() && (ch.is_whitespace() || is_operator_start(ch));
+    }
+
+    quote
+}
+
+// words and operators in input order, `[[ ... ]]` switches the tokenizer to
+// conditional mode where operators are plain words
+struct Lexed {
+    tokens: Vec<(String, bool)>, // (token, is_operator)
+    cond: bool,
+    // the word after =~ is a regex, parens and | don't end it
+    regex: bool,
+}
+
+impl Lexed {
+    fn push_word(&mut self, word: &mut String) {
+        if word.is_empty() {
+            return;
+        }
+
+        let at_cmd_start = match self.tokens.last() {
+            Some((tok, is_op)) => *is_op && tok != ")" || (!*is_op && tok == "!"),
+            None => true,
+        };
+        self.regex = false;
+        if !self.cond && word == "[[" && at_cmd_start {
+            self.cond = true;
+        } else if self.cond && word == "]]" {
+            self.cond = false;
+        } else if self.cond && word == "=~" {
+            self.regex = true;
+        }
+
+        self.tokens.push((std::mem::take(word), false));
+    }
+
+    fn push_operator(&mut self, operator: String) {
+        let is_op = !self.cond;
+        self.tokens.push((operator, is_op));
+    }
+}
+
+fn tokenize_to_strings(input: &str) -> Vec<(String, bool)> {
+    let mut lexed = Lexed {
+        tokens: Vec::new(),
+        cond: false,
+        regex: false,
+    };
     let mut current_token = String::new();
     let chars = input.chars().collect::<Vec<_>>();
     let mut i = 0;
+    let mut regex_depth = 0;
 
     #[derive(Debug)]
     enum State {
@@ -62,45 +147,65 @@ fn tokenize_to_strings(input: &str) -> Vec<String> {
 
         match state {
             State::Unquoted => {
+                // quotes and escapes are kept in the word, they are removed
+                // during expansion
                 if ch == '\'' {
                     state = State::SingleQuoted;
+                    current_token.push(ch);
                     i += 1;
                 } else if ch == '"' {
                     state = State::DoubleQuoted;
+                    current_token.push(ch);
                     i += 1;
                 } else if ch == '\\' {
+                    current_token.push(ch);
                     i += 1;
                     if i < chars.len() {
                         current_token.push(chars[i]);
                         i += 1;
                     }
-                } else if ch.is_whitespace() {
-                    if !current_token.is_empty() {
-                        tokens.push(current_token.clone());
-                        current_token.clear();
+                } else if lexed.regex && !(ch.is_whitespace() && regex_depth == 0) {
+                    match ch {
+                        '(' => regex_depth += 1,
+                        ')' => regex_depth -= 1,
+                        _ => {}
                     }
+                    current_token.push(ch);
                     i += 1;
+                } else if ch == '#' && current_token.is_empty() {
+                    // a comment runs to the end of the line
+                    while i < chars.len() && chars[i] != '\n' {
+                        i += 1;
+                    }
+                } else if ch == '$' && chars.get(i + 1) == Some(&'{') {
+                    // ${name:-a b} is kept in one word
+                    let len = 1 + compound_len(&chars[i + 1..], '{', '}');
+                    current_token.extend(&chars[i..i + len]);
+                    i += len;
+                } else if ch.is_whitespace() {
+                    lexed.push_word(&mut current_token);
+                    regex_depth = 0;
+                    i += 1;
+                } else if ch == '(' && is_array_assign(&current_token) {
+                    // name=(a b c) is kept as a single word
+                    let len = compound_len(&chars[i..], '(', ')');
+                    current_token.extend(&chars[i..i + len]);
+                    i += len;
                 } else if is_operator_start(ch) {
                     // handle operators
                     let operator = extract_operator(&chars[i..]);
-                    if !current_token.is_empty() {
-                        tokens.push(current_token.clone());
-                        current_token.clear();
-                    }
-                    tokens.push(operator.clone());
+                    lexed.push_word(&mut current_token);
                     i += operator.len();
-                } else if ch == '1' || ch == '2' {
+                    lexed.push_operator(operator);
+                } else if (ch == '1' || ch == '2') && !lexed.cond {
                     if i + 1 < chars.len() && chars[i + 1] == '>' {
                         let mut operator = format!("{}>", ch);
                         if i + 2 < chars.len() && chars[i + 2] == '>' {
                             operator.push('>');
                         }
-                        if !current_token.is_empty() {
-                            tokens.push(current_token.clone());
-                            current_token.clear();
-                        }
-                        tokens.push(operator.clone());
+                        lexed.push_word(&mut current_token);
                         i += operator.len();
+                        lexed.push_operator(operator);
                     } else {
                         current_token.push(ch);
                         i += 1;
@@ -113,6 +218,7 @@ fn tokenize_to_strings(input: &str) -> Vec<String> {
             State::SingleQuoted => {
                 if ch == '\'' {
                     state = State::Unquoted;
+                    current_token.push(ch);
                     i += 1;
                 } else {
                     current_token.push(ch);
@@ -122,19 +228,14 @@ fn tokenize_to_strings(input: &str) -> Vec<String> {
             State::DoubleQuoted => {
                 if ch == '"' {
                     state = State::Unquoted;
+                    current_token.push(ch);
                     i += 1;
                 } else if ch == '\\' {
+                    current_token.push(ch);
                     i += 1;
                     if i < chars.len() {
-                        let next_ch = chars[i];
-                        if matches!(next_ch, '\\' | '"' | '$' | '`' | '\n') {
-                            if next_ch != '\n' {
-                                current_token.push(next_ch);
-                            }
-                            i += 1;
-                        } else {
-                            current_token.push('\\');
-                        }
+                        current_token.push(chars[i]);
+                        i += 1;
                     }
                 } else {
                     current_token.push(ch);
@@ -144,15 +245,55 @@ fn tokenize_to_strings(input: &str) -> Vec<String> {
         }
     }
 
-    if !current_token.is_empty() {
-        tokens.push(current_token);
+    lexed.push_word(&mut current_token);
+
+    lexed.tokens
+}
+
+fn is_array_assign(word: &str) -> bool {
+    let Some(name) = word.strip_suffix('=') else {
+        return false;
+    };
+    is_name(name.strip_suffix('+').unwrap_or(name))
+}
+
+// length of a bracketed compound up to and including the matching close
+fn compound_len(chars: &[char], open: char, close: char) -> usize {
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let ch = chars[i];
+        match quote {
+            Some(q) => {
+                if ch == q {
+                    quote = None;
+                } else if ch == '\\' && q == '"' {
+                    i += 1;
+                }
+            }
+            None => match ch {
+                '\'' | '"' => quote = Some(ch),
+                '\\' => i += 1,
+                c if c == open => depth += 1,
+                c if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return i + 1;
+                    }
+                }
+                _ => {}
+            },
+        }
+        i += 1;
     }
 
-    tokens
+    chars.len()
 }
 
 fn is_operator_start(ch: char) -> bool {
-    matches!(ch, '>' | '&' | '|' | ';' | '(' | ')')
+    matches!(ch, '<' | '>' | '&' | '|' | ';' | '(' | ')')
 }
 
 fn extract_operator(chars: &[char]) -> String {
@@ -178,8 +319,8 @@ fn extract_operator(chars: &[char]) -> String {
         // simple redirects
         '>' => {
             let mut result = ">".to_string();
-            if chars.len() > 1 && chars[1] == '>' {
-                result.push('>');
+            if chars.len() > 1 && matches!(chars[1], '>' | '|') {
+                result.push(chars[1]);
             }
             result
         }
@@ -210,25 +351,32 @@ fn extract_operator(chars: &[char]) -> String {
             }
         }
         // single character operators
-        ';' | '(' | ')' => first.to_string(),
+        '<' | ';' | '(' | ')' => first.to_string(),
         // default case (shouldn't happen with is_operator_start)
         _ => first.to_string(),
     }
 }
 
-fn convert_to_tokens(string_tokens: &[String]) -> Vec<Token> {
+fn convert_to_tokens(string_tokens: &[(String, bool)]) -> Vec<Token> {
     let mut tokens = Vec::new();
 
-    for token in string_tokens {
+    for (token, is_operator) in string_tokens {
+        if !is_operator {
+            tokens.push(Token::Word(token.clone()));
+            continue;
+        }
+
         match token.as_str() {
             "|" => tokens.push(Token::Pipe),
             "||" => tokens.push(Token::DoublePipe),
             "&" => tokens.push(Token::And),
             "&&" => tokens.push(Token::DoubleAnd),
+            "<" => tokens.push(Token::RedirectIn),
             ">" => tokens.push(Token::RedirectOut),
             "1>" => tokens.push(Token::RedirectOut),
             ">>" => tokens.push(Token::AppendOut),
             "1>>" => tokens.push(Token::AppendOut),
+            ">|" => tokens.push(Token::Clobber),
             "2>" => tokens.push(Token::RedirectErr),
             "2>>" => tokens.push(Token::AppendErr),
             "&>" => tokens.push(Token::RedirectBoth),
@@ -244,3 +392,71 @@ fn convert_to_tokens(string_tokens: &[String]) -> Vec<Token> {
 
     tokens
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(input: &str) -> Vec<String> {
+        tokenize(input).iter().map(|tok| tok.to_string()).collect()
+    }
+
+    // the words of the input, operators in angle brackets
+    fn lexed(input: &str) -> Vec<String> {
+        tokenize_to_strings(input)
+            .into_iter()
+            .map(|(tok, is_op)| if is_op { format!("<{}>", tok) } else { tok })
+            .collect()
+    }
+
+    #[test]
+    fn braced_parameters_stay_one_word() {
+        assert_eq!(words("echo ${v:-a b}x y"), ["echo", "${v:-a b}x", "y"]);
+        assert_eq!(words("echo ${v:-${w#* }}"), ["echo", "${v:-${w#* }}"]);
+    }
+
+    #[test]
+    fn operators_are_words_inside_double_brackets() {
+        assert_eq!(
+            lexed("[[ a < b && (c) ]] && echo >x"),
+            [
+                "[[", "a", "<", "b", "&&", "(", "c", ")", "]]", "<&&>", "echo", "<>>", "x"
+            ]
+        );
+        assert_eq!(lexed("! [[ a ]]|x"), ["!", "[[", "a", "]]", "<|>", "x"]);
+        // only at the start of a command
+        assert_eq!(lexed("echo [[ a < b"), ["echo", "[[", "a", "<<>", "b"]);
+    }
+
+    #[test]
+    fn regex_after_match_operator() {
+        assert_eq!(
+            lexed("[[ x =~ ^(a|b c)+$ ]]"),
+            ["[[", "x", "=~", "^(a|b c)+$", "]]"]
+        );
+        assert_eq!(
+            lexed("[[ x =~ a\\ b || y ]]"),
+            ["[[", "x", "=~", "a\\ b", "||", "y", "]]"]
+        );
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(words("echo a # b c"), ["echo", "a"]);
+        assert_eq!(words("echo a#b '#c' \\#d"), ["echo", "a#b", "'#c'", "\\#d"]);
+        assert_eq!(words("echo a;# b"), ["echo", "a", ";"]);
+        assert_eq!(unterminated("echo a # it's"), None);
+        assert_eq!(unterminated("echo 'a"), Some('\''));
+        assert_eq!(unterminated("echo a\\"), Some('\\'));
+    }
+
+    #[test]
+    fn array_assignments_stay_one_word() {
+        assert_eq!(
+            words("arr=(a 'b c' [k]=\"d e\") x"),
+            ["arr=(a 'b c' [k]=\"d e\")", "x"]
+        );
+        assert_eq!(words("arr+=(a) x"), ["arr+=(a)", "x"]);
+        assert_eq!(words("echo x=(a)"), ["echo", "x=(a)"]);
+    }
+}
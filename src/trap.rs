@@ -0,0 +1,80 @@
+use crate::{
+    run_lines,
+    signals::{Disposition, set_disposition, signal_name, signal_number, take_pending},
+    state::with_shell,
+};
+
+// traps that fire on shell events rather than signals
+pub const PSEUDO_SIGNALS: &[&str] = &["EXIT", "DEBUG", "RETURN", "ERR"];
+
+/// The trap table key for a signal spec: EXIT, ERR, DEBUG, RETURN or a
+/// signal name without the SIG prefix. `0` is EXIT.
+pub fn trap_name(spec: &str) -> Option<String> {
+    let upper = spec.to_ascii_uppercase();
+    if spec == "0" || PSEUDO_SIGNALS.contains(&upper.as_str()) {
+        return Some(if spec == "0" {
+            "EXIT".to_string()
+        } else {
+            upper
+        });
+    }
+    let num = signal_number(spec).filter(|&num| num != 0)?;
+    signal_name(num).map(String::from)
+}
+
+/// Installs (Some) or resets (None) the action of a trap, an empty action
+/// ignores the signal.
+pub fn set_trap(name: &str, action: Option<String>) {
+    if !PSEUDO_SIGNALS.contains(&name)
+        && let Some(num) = signal_number(name)
+    {
+        let disposition = match action.as_deref() {
+            Some("") => Disposition::Ignore,
+            Some(_) => Disposition::Catch,
+            None => Disposition::Default,
+        };
+        set_disposition(num, disposition);
+    }
+
+    with_shell(|shell| match action {
+        Some(action) => shell.traps.insert(name.to_string(), action),
+        None => shell.traps.remove(name),
+    });
+}
+
+/// Runs the commands of a trap. Traps don't fire while another trap runs.
+pub fn run_trap(name: &str) {
+    let action = with_shell(|shell| {
+        if shell.in_trap {
+            return None;
+        }
+        shell.traps.get(name).cloned()
+    });
+    if let Some(action) = action {
+        run_action(&action);
+    }
+}
+
+// `$?` is kept as it was before the trap, and a RETURN trap still runs
+// while its file is returning
+pub fn run_action(action: &str) {
+    let (status, returning) = with_shell(|shell| {
+        shell.in_trap = true;
+        (shell.last_status, std::mem::take(&mut shell.returning))
+    });
+    run_lines(action.lines().map(String::from));
+    with_shell(|shell| {
+        shell.in_trap = false;
+        shell.last_status = status;
+        shell.returning |= returning;
+    });
+}
+
+// runs the traps of signals caught since the last safe point
+pub fn run_pending() {
+    for sig in take_pending() {
+        if let Some(name) = signal_name(sig) {
+            run_trap(name);
+        }
+    }
+}
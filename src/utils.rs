@@ -1,6 +1,9 @@
 use std::{
-    env, fs,
+    env,
+    ffi::CString,
+    fs,
     io::Write,
+    mem,
     path::{Path, PathBuf},
 };
 
@@ -27,7 +30,7 @@ pub fn check_ext_cmd(cmd: &str) -> (bool, Option<PathBuf>) {
             }
         }
     }
-    return (false, None);
+    (false, None)
 }
 
 pub fn write_to_file(output: Vec<u8>, filename: String) {
@@ -51,7 +54,6 @@ pub fn write_to_file(output: Vec<u8>, filename: String) {
 
 pub fn append_to_file(output: Vec<u8>, filename: String) {
     let mut file = match fs::OpenOptions::new()
-        .write(true)
         .create(true)
         .append(true)
         .open(filename.clone())
@@ -67,3 +69,168 @@ pub fn append_to_file(output: Vec<u8>, filename: String) {
         eprintln!("Could not write to {}: {}", filename.clone(), e);
     }
 }
+
+pub fn pattern_match(pattern: &str, s: &str) -> bool {
+    let (Ok(pattern), Ok(s)) = (CString::new(pattern), CString::new(s)) else {
+        return false;
+    };
+    unsafe { libc::fnmatch(pattern.as_ptr(), s.as_ptr(), 0) == 0 }
+}
+
+/// Matches `s` against a POSIX extended regex, returning the whole match
+/// followed by each capture group.
+pub fn regex_match(regex: &str, s: &str) -> Result<Option<Vec<String>>, String> {
+    let (Ok(c_regex), Ok(c_s)) = (CString::new(regex), CString::new(s)) else {
+        return Ok(None);
+    };
+
+    unsafe {
+        let mut re: libc::regex_t = mem::zeroed();
+        if libc::regcomp(&mut re, c_regex.as_ptr(), libc::REG_EXTENDED) != 0 {
+            return Err(format!("{}: invalid regular expression", regex));
+        }
+
+        let nmatch = count_groups(regex) + 1;
+        let mut matches = vec![
+            libc::regmatch_t {
+                rm_so: -1,
+                rm_eo: -1
+            };
+            nmatch
+        ];
+        let found = libc::regexec(&re, c_s.as_ptr(), nmatch, matches.as_mut_ptr(), 0) == 0;
+        libc::regfree(&mut re);
+
+        if !found {
+            return Ok(None);
+        }
+
+        // the offsets are bytes and may split a multibyte character
+        let groups = matches
+            .iter()
+            .map(|m| {
+                if m.rm_so < 0 {
+                    String::new()
+                } else {
+                    let bytes = &s.as_bytes()[m.rm_so as usize..m.rm_eo as usize];
+                    String::from_utf8_lossy(bytes).into_owned()
+                }
+            })
+            .collect();
+        Ok(Some(groups))
+    }
+}
+
+// number of capture groups, regex_t keeps re_nsub private
+fn count_groups(regex: &str) -> usize {
+    let mut count = 0;
+    let mut in_bracket = false;
+    let mut chars = regex.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                chars.next();
+            }
+            '[' if !in_bracket => {
+                in_bracket = true;
+                // a leading ] is part of the bracket expression
+                if chars.peek() == Some(&'^') {
+                    chars.next();
+                }
+                if chars.peek() == Some(&']') {
+                    chars.next();
+                }
+            }
+            ']' if in_bracket => in_bracket = false,
+            '(' if !in_bracket => count += 1,
+            _ => {}
+        }
+    }
+
+    count
+}
+
+// "No such process (os error 3)" -> "No such process"
+pub fn strip_os_error(err: &std::io::Error) -> String {
+    let msg = err.to_string();
+    match msg.find(" (os error") {
+        Some(i) => msg[..i].to_string(),
+        None => msg,
+    }
+}
+
+/// Reads a line from the fd a byte at a time, so nothing past the newline
+/// is taken from a shared input. None at end of input.
+pub fn read_line(fd: i32) -> Option<String> {
+    let mut line = Vec::new();
+    let mut byte = 0u8;
+    loop {
+        let n = unsafe { libc::read(fd, (&mut byte as *mut u8).cast(), 1) };
+        if n < 0 && std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+            continue;
+        }
+        if n <= 0 {
+            break;
+        }
+        if byte == b'\n' {
+            return Some(String::from_utf8_lossy(&line).into_owned());
+        }
+        line.push(byte);
+    }
+    (!line.is_empty()).then(|| String::from_utf8_lossy(&line).into_owned())
+}
+
+/// Quotes a word so the shell reads it back unchanged, used for traces and
+/// listings.
+pub fn quote_word(word: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_./=:@%+,-".contains(c);
+    if !word.is_empty() && word.chars().all(safe) {
+        return word.to_string();
+    }
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+// pathnames matching a glob pattern, sorted, empty if nothing matches
+pub fn glob_paths(pattern: &str) -> Vec<String> {
+    let Ok(c_pattern) = CString::new(pattern) else {
+        return Vec::new();
+    };
+
+    let mut paths = Vec::new();
+    unsafe {
+        let mut glob: libc::glob_t = mem::zeroed();
+        if libc::glob(c_pattern.as_ptr(), 0, None, &mut glob) == 0 {
+            for i in 0..glob.gl_pathc {
+                let path = std::ffi::CStr::from_ptr(*glob.gl_pathv.add(i));
+                paths.push(path.to_string_lossy().into_owned());
+            }
+        }
+        libc::globfree(&mut glob);
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regex_match_groups() {
+        let groups = regex_match("^([a-z]+)-([0-9]+)$", "abc-42").unwrap();
+        assert_eq!(
+            groups,
+            Some(vec!["abc-42".into(), "abc".into(), "42".into()])
+        );
+        assert_eq!(regex_match("^x", "abc").unwrap(), None);
+    }
+
+    #[test]
+    fn regex_match_non_ascii() {
+        let groups = regex_match("^(é)-(ü)$", "é-ü").unwrap();
+        assert_eq!(groups, Some(vec!["é-ü".into(), "é".into(), "ü".into()]));
+        // a match ending inside a character doesn't panic
+        let groups = regex_match("^.", "é").unwrap().unwrap();
+        assert_eq!(groups.len(), 1);
+    }
+}
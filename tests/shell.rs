@@ -0,0 +1,158 @@
+use std::{
+    io::Write,
+    os::unix::fs::PermissionsExt,
+    process::{Command, Output, Stdio},
+};
+
+// runs the commands with `shell -c`
+fn run(commands: &str) -> Output {
+    run_with_input(commands, "")
+}
+
+fn run_with_input(commands: &str, input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_shell"))
+        .args(["-c", commands])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn builtin_errors_go_to_stderr() {
+    let output = run("[ 1 -eq ]; echo $?; umask 999");
+    assert_eq!(stdout(&output), "2\n");
+    assert_eq!(
+        stderr(&output),
+        "[: -eq: unexpected argument\numask: 999: invalid mode\n"
+    );
+
+    let output = run("[ 1 -eq ] 2>/dev/null; echo $?");
+    assert_eq!(stdout(&output), "2\n");
+    assert_eq!(stderr(&output), "");
+}
+
+#[test]
+fn background_jobs_read_dev_null() {
+    let output = run_with_input("cat & wait; read line; echo \"<$line>\"", "data\n");
+    assert_eq!(stdout(&output), "<data>\n");
+}
+
+#[test]
+fn wait_n_leaves_the_pipeline_statuses() {
+    let output = run("sleep 0.3 & sh -c \"exit 3\" | wait -n; echo ${PIPESTATUS[@]}");
+    assert_eq!(stdout(&output), "3 0\n");
+}
+
+#[test]
+fn trapped_signals_dont_lose_the_status() {
+    let output =
+        run("trap 'echo trapped' USR1; sh -c 'sleep 0.1; kill -USR1 $PPID; exit 4'; echo $?");
+    assert_eq!(stdout(&output), "trapped\n4\n");
+}
+
+#[test]
+fn forked_children_dont_run_the_exit_trap() {
+    let output = run("trap 'echo bye' EXIT; exit 3 | cat; exit 4 & wait; echo x | cat");
+    assert_eq!(stdout(&output), "x\nbye\n");
+}
+
+#[test]
+fn scripts_without_a_shebang_run_in_the_shell() {
+    let dir = std::env::temp_dir().join(format!("shell-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("no-shebang");
+    std::fs::write(&script, "[[ $1 == a* ]] && echo \"matched $1\"\n").unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = run(&format!("{} abc", script.display()));
+    assert_eq!(stdout(&output), "matched abc\n");
+    let output = run(&format!("PATH={}:$PATH no-shebang ab", dir.display()));
+    assert_eq!(stdout(&output), "matched ab\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn errexit_nounset_and_noglob() {
+    let output = run("set -e; echo $-; false; echo not reached");
+    assert_eq!(stdout(&output), "e\n");
+    assert_eq!(output.status.code(), Some(1));
+    let output = run("set -f; echo /*; set +f -u; echo $shell_test_unset; echo not reached");
+    assert_eq!(stdout(&output), "/*\n");
+    assert!(stderr(&output).contains("shell_test_unset: unbound variable"));
+}
+
+#[test]
+fn cd_dirs_and_options() {
+    let dir = std::env::temp_dir().join(format!("shell-cd-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("real/sub")).unwrap();
+    let _ = std::os::unix::fs::symlink(dir.join("real"), dir.join("link"));
+    let dir = dir.canonicalize().unwrap();
+    let d = dir.display();
+
+    // -L keeps the symlink, -P resolves it, .. is taken logically
+    let output = run(&format!(
+        "cd {d}/link/sub; echo $PWD; cd ..; echo $PWD; cd -P {d}/link; echo $PWD"
+    ));
+    assert_eq!(
+        stdout(&output),
+        format!("{d}/link/sub\n{d}/link\n{d}/real\n")
+    );
+
+    // cd - goes back to OLDPWD and prints it
+    let output = run(&format!("cd {d}; cd real; echo $OLDPWD; cd -; echo $PWD"));
+    assert_eq!(stdout(&output), format!("{d}\n{d}\n{d}\n"));
+
+    // a CDPATH hit is printed, also when CDPATH only prefixes the command
+    let output = run(&format!(
+        "cd /; CDPATH={d}/real cd sub; echo $PWD; echo \"<$CDPATH>\""
+    ));
+    assert_eq!(stdout(&output), format!("{d}/real/sub\n{d}/real/sub\n<>\n"));
+    let output = run(&format!("cd /; CDPATH=:{d} cd real"));
+    assert_eq!(stdout(&output), format!("{d}/real\n"));
+
+    let output = run("OLDPWD= cd -; cd /nonexistent 2>/dev/null; echo $?");
+    assert_eq!(stdout(&output), "1\n");
+    assert_eq!(stderr(&output), "cd: OLDPWD not set\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn source_takes_arguments_and_return() {
+    let dir = std::env::temp_dir().join(format!("shell-source-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("lib.sh");
+    std::fs::write(&file, "echo \"$# $1 $2\"\nreturn 3\necho not reached\n").unwrap();
+    let f = file.display();
+
+    // arguments replace $@ while the file runs, without them it sees the caller's
+    let output = run(&format!(
+        "set -- x; source {f} a b; echo $? $1; . {f}; echo $?"
+    ));
+    assert_eq!(stdout(&output), "2 a b\n3 x\n1 x \n3\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn exit_warns_about_stopped_jobs_once() {
+    let output = run("sleep 5 & kill -STOP $!; sleep 0.1; exit 2; echo still here; exit 5");
+    assert_eq!(stdout(&output), "still here\n");
+    assert_eq!(stderr(&output), "There are stopped jobs.\n");
+    assert_eq!(output.status.code(), Some(5));
+}